@@ -7,7 +7,7 @@ use std::{fs::File, io::Read, path::Path};
 const RESULT_URL: &str = "/pingcap/qa/tests/corrupttest/res.csv";
 const RESULT_FILENAME: &str = "res.csv";
 
-// sort by mutation checker, assertion, injection, workload
+// sort by mutation checker, assertion, session, term, injection, workload
 fn main() -> Result<()> {
     let matches = App::new("corrupttest")
         .arg(
@@ -46,6 +46,8 @@ fn process_results(mut records: Vec<Record>, local: bool) -> Result<()> {
         (
             r.mutation_checker.clone(),
             r.assertion.clone(),
+            r.session.clone(),
+            r.term.clone(),
             r.injection.clone(),
             r.workload.clone(),
         )
@@ -98,9 +100,11 @@ fn process_results(mut records: Vec<Record>, local: bool) -> Result<()> {
         if !diffs.is_empty() {
             for (old, new) in &diffs {
                 println!(
-                    "mutation_checker:{}, assertion:{}, injection:{}, workload:{}, {} -> {}",
+                    "mutation_checker:{}, assertion:{}, session:{}, term:{}, injection:{}, workload:{}, {} -> {}",
                     old.mutation_checker,
                     old.assertion,
+                    old.session,
+                    old.term,
                     old.injection,
                     old.workload,
                     old.effective_rate,
@@ -148,15 +152,33 @@ fn write_result(records: &Vec<Record>) -> Result<()> {
 struct Record {
     mutation_checker: String,
     assertion: String,
+    #[serde(default)]
+    session: String,
+    #[serde(default = "default_term")]
+    term: String,
     injection: String,
     workload: String,
     success: u32,
+    #[serde(default)]
+    read_success: u32,
     other_error: u32,
     failure: u32,
     consistent: u32,
+    #[serde(default)]
+    undetected: u32,
+    #[serde(default)]
+    succeeded_jobs: u32,
+    #[serde(default)]
+    total_jobs: u32,
+    #[serde(default)]
+    txn_size: u32,
     effective_rate: f32,
 }
 
+fn default_term() -> String {
+    "default".to_owned()
+}
+
 fn one_file(filepath: impl AsRef<Path>) -> Result<Vec<Record>> {
     let mut file = File::open(filepath)?;
     let mut content = String::new();
@@ -167,36 +189,77 @@ fn one_file(filepath: impl AsRef<Path>) -> Result<Vec<Record>> {
     // 2nd line, the config
     lines.next().unwrap();
     let line = lines.next().unwrap();
-    let re = Regex::new(r#"workload_name: "(.*?)", mutation_checker: "(.*?)", assertion: "(.*?)""#)
-        .unwrap();
+    let re = Regex::new(r#"mutation_checker: "(.*?)", assertion: "(.*?)""#).unwrap();
     let captures = re.captures(line).unwrap();
-    let workload_name = captures.get(1).unwrap().as_str().to_owned();
-    let mutation_checker = captures.get(2).unwrap().as_str().to_owned();
-    let assertion = captures.get(3).unwrap().as_str().to_owned();
+    let mutation_checker = captures.get(1).unwrap().as_str().to_owned();
+    let assertion = captures.get(2).unwrap().as_str().to_owned();
+    // logs written before several workloads could run at once name the only one in the config
+    let workload_name = Regex::new(r#"workload_name: "(.*?)""#)
+        .unwrap()
+        .captures(line)
+        .map(|c| c.get(1).unwrap().as_str().to_owned());
 
-    // the last 4 lines
-    let lines = lines.rev().take(4);
-    for line in lines {
-        let re = Regex::new(
-            r#"\]\s+(.*?):\s+success:(\d+)\s+other success:(\d+)\s+failure:(\d+)\s+consistent:(\d+).*"#,
-        )
-        .unwrap();
-        let captures = re.captures(line).unwrap();
+    // the summary lines, one for each workload, session, term and injection
+    let re = Regex::new(
+        r#"\]\s+(.*?):\s+success:(\d+)\s+other success:(\d+)\s+failure:(\d+)\s+consistent:(\d+)(?:\s+read success:(\d+))?(?:\s+session:(\S+))?(?:\s+term:(\S+))?(?:\s+undetected:(\d+))?(?:\s+workload:(\S+))?(?:\s+succeeded jobs:(?:(\d+)/(\d+))?)?(?:\s+txn size:(\d+))?.*"#,
+    )
+    .unwrap();
+    for captures in lines.filter_map(|line| re.captures(line)) {
         let injection = captures.get(1).unwrap().as_str().to_owned();
         let success = captures.get(2).unwrap().as_str().parse::<u32>().unwrap();
         let other_error = captures.get(3).unwrap().as_str().parse::<u32>().unwrap();
         let failure = captures.get(4).unwrap().as_str().parse::<u32>().unwrap();
         let consistent = captures.get(5).unwrap().as_str().parse::<u32>().unwrap();
-        let effective_rate = success as f32 / (success + failure) as f32;
+        // logs written before the read path was tested don't have this field
+        let read_success = captures
+            .get(6)
+            .map_or(0, |m| m.as_str().parse::<u32>().unwrap());
+        // logs written before sessions were introduced have a single, unnamed one
+        let session = captures
+            .get(7)
+            .map_or_else(String::new, |m| m.as_str().to_owned());
+        // and the default term
+        let term = captures
+            .get(8)
+            .map_or_else(|| "default".to_owned(), |m| m.as_str().to_owned());
+        // and the MVCC check
+        let undetected = captures
+            .get(9)
+            .map_or(0, |m| m.as_str().parse::<u32>().unwrap());
+        let workload = captures
+            .get(10)
+            .map(|m| m.as_str().to_owned())
+            .or_else(|| workload_name.clone())
+            .unwrap_or_default();
+        // only non-transactional DML has jobs
+        let succeeded_jobs = captures
+            .get(11)
+            .map_or(0, |m| m.as_str().parse::<u32>().unwrap());
+        let total_jobs = captures
+            .get(12)
+            .map_or(0, |m| m.as_str().parse::<u32>().unwrap());
+        // only the large txn workload has a txn size
+        let txn_size = captures
+            .get(13)
+            .map_or(0, |m| m.as_str().parse::<u32>().unwrap());
+        let effective_rate = (success + read_success) as f32
+            / (success + read_success + failure + undetected) as f32;
         let record = Record {
             mutation_checker: mutation_checker.clone(),
             assertion: assertion.clone(),
+            session,
+            term,
             injection,
-            workload: workload_name.clone(),
+            workload,
             success,
+            read_success,
             other_error,
             failure,
             consistent,
+            undetected,
+            succeeded_jobs,
+            total_jobs,
+            txn_size,
             effective_rate,
         };
         res.push(record);
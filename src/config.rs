@@ -1,19 +1,250 @@
 use clap::{App, Arg};
+use std::fmt;
 
+use crate::injection::Injection;
+use crate::table::DISTINCT_VALUES;
 use crate::workload::WORKLOADS;
 
+// how a txn commits. Assertions are checked in prewrite, which happens at different points
+// for different protocols.
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
+pub enum CommitProtocol {
+    TwoPhase,
+    AsyncCommit,
+    OnePhase,
+}
+
+impl CommitProtocol {
+    const NAMES: &'static [&'static str] = &["2pc", "async_commit", "1pc"];
+
+    fn from_name(name: &str) -> Self {
+        match name {
+            "2pc" => CommitProtocol::TwoPhase,
+            "async_commit" => CommitProtocol::AsyncCommit,
+            "1pc" => CommitProtocol::OnePhase,
+            _ => unreachable!(),
+        }
+    }
+}
+
+impl fmt::Display for CommitProtocol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            CommitProtocol::TwoPhase => "2pc",
+            CommitProtocol::AsyncCommit => "async_commit",
+            CommitProtocol::OnePhase => "1pc",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+// how DML statements are sent. The binary protocol goes through prepared statements and the
+// plan cache.
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
+pub enum StatementProtocol {
+    Text,
+    Binary,
+}
+
+impl StatementProtocol {
+    const NAMES: &'static [&'static str] = &["text", "binary"];
+
+    fn from_name(name: &str) -> Self {
+        match name {
+            "text" => StatementProtocol::Text,
+            "binary" => StatementProtocol::Binary,
+            _ => unreachable!(),
+        }
+    }
+}
+
+impl fmt::Display for StatementProtocol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            StatementProtocol::Text => "text",
+            StatementProtocol::Binary => "binary",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+// session-level settings a workload runs with. Every table is tested with each combination
+// of them, see `Config::sessions`.
+#[derive(Clone, Debug, Hash, PartialEq, Eq)]
+pub struct Session {
+    pub commit_protocol: CommitProtocol,
+    pub protocol: StatementProtocol,
+    // arbitrary system variables given by `--set`, as (name, value)
+    pub vars: Vec<(String, String)>,
+}
+
+impl fmt::Display for Session {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{},{}", self.commit_protocol, self.protocol)?;
+        for (name, value) in &self.vars {
+            write!(f, ",{}={}", name, value)?;
+        }
+        Ok(())
+    }
+}
+
+// How the failpoint is evaluated, e.g. `1*return`, `50%return`, `2*off->1*return` or
+// `1*delay(100)->1*return`. The `return` in it is replaced by the action of the injection. The
+// default term is chosen by each workload.
+#[derive(Clone, Debug, Hash, PartialEq, Eq)]
+pub enum Term {
+    Default,
+    Custom(String),
+}
+
+impl Term {
+    pub fn template<'a>(&'a self, default: &'a str) -> &'a str {
+        match self {
+            Term::Default => default,
+            Term::Custom(term) => term,
+        }
+    }
+}
+
+// Splits a failpoint term into its tasks, which are chained by `->`. Each task is split into its
+// prefix of a percentage and a count, e.g. `50%1*`, and its action, e.g. `return`.
+pub fn split_term(term: &str) -> Vec<(&str, &str)> {
+    term.split("->")
+        .map(|task| {
+            let task = task.trim();
+            let at = task
+                .find(|c: char| !(c.is_ascii_digit() || matches!(c, '.' | '%' | '*')))
+                .unwrap_or(task.len());
+            task.split_at(at)
+        })
+        .collect()
+}
+
+impl fmt::Display for Term {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.template("default"))
+    }
+}
+
+// what to do with the cases
+#[derive(Debug)]
+pub enum Command {
+    // run every table with the workload
+    Run,
+    // print a script that reproduces one case, see `repro::repro`
+    Repro { table: String, injection: String },
+    // run one case with verbose logging
+    Replay { table: String, injection: String },
+}
+
 #[derive(Debug)]
 pub struct Config {
-    pub workload_name: String,
+    pub workloads: Vec<String>,
     pub mutation_checker: String,
     pub assertion: String,
     pub limit: u32,
     pub uri: String,
-    pub status_addr: String,
+    pub status_addrs: Vec<String>,
+    // TiKV injections are skipped if it's empty
+    pub tikv_status_addrs: Vec<String>,
+    pub failpoint_timeout_ms: u64,
+    // retries of a failed failpoint request
+    pub failpoint_retries: u32,
     pub log_path: String,
+    pub txn_size: u32,
+    pub inject_at: u32,
+    pub commit_protocols: Vec<CommitProtocol>,
+    pub protocols: Vec<StatementProtocol>,
+    // system variables given by `--set name=v1,v2`, as (name, candidate values)
+    pub vars: Vec<(String, Vec<String>)>,
+    // seed of the fuzz workload, each case derives one from a random seed if it's not given
+    pub seed: Option<u64>,
+    // minimise failing cases and write the reproducers to `shrink_dir`
+    pub shrink: bool,
+    pub shrink_dir: String,
+    // look for corruption with the MVCC API when `admin check table` finds none
+    pub mvcc: bool,
+    // failpoint terms, each of them is a matrix dimension
+    pub terms: Vec<Term>,
+    pub command: Command,
+}
+
+impl Config {
+    pub fn sessions(&self) -> Vec<Session> {
+        let mut sessions = self
+            .commit_protocols
+            .iter()
+            .flat_map(|&commit_protocol| {
+                self.protocols.iter().map(move |&protocol| Session {
+                    commit_protocol,
+                    protocol,
+                    vars: vec![],
+                })
+            })
+            .collect::<Vec<_>>();
+        for (name, values) in &self.vars {
+            sessions = sessions
+                .into_iter()
+                .flat_map(|session| {
+                    values.iter().map(move |value| {
+                        let mut session = session.clone();
+                        session.vars.push((name.clone(), value.clone()));
+                        session
+                    })
+                })
+                .collect();
+        }
+        sessions
+    }
+}
+
+// arguments of subcommands that work on a single case
+fn case_args(app: App) -> App {
+    app.arg(
+        Arg::new("table").required(true).help(
+            "name of the table, its index in the generated tables, or a CREATE TABLE statement",
+        ),
+    )
+    .arg(
+        Arg::new("injection")
+            .required(true)
+            .possible_values(Injection::names()),
+    )
+}
+
+// Every `return` of a term is replaced by the injection. A term without one would run the case
+// with nothing injected, which looks consistent, and the arguments of `return` come from the
+// injection.
+fn validate_term(term: &str) -> std::result::Result<(), String> {
+    let actions = split_term(term)
+        .into_iter()
+        .map(|(_, action)| action)
+        .collect::<Vec<_>>();
+    if actions.iter().any(|action| action.starts_with("return(")) {
+        return Err(format!(
+            "term {} gives arguments to `return`, they are given by the injection",
+            term
+        ));
+    }
+    if !actions.contains(&"return") {
+        return Err(format!(
+            "term {} has no `return` to be replaced by the injection",
+            term
+        ));
+    }
+    Ok(())
 }
 
 pub fn init_app() -> Config {
+    parse_args(std::env::args_os())
+}
+
+// parse the command line `args`, the first of which is the binary
+pub fn parse_args<I, T>(args: I) -> Config
+where
+    I: IntoIterator<Item = T>,
+    T: Into<std::ffi::OsString> + Clone,
+{
     let matches = App::new("corrupttest")
         .arg(
             Arg::new("workload")
@@ -21,7 +252,10 @@ pub fn init_app() -> Config {
                 .long("workload")
                 .takes_value(true)
                 .required(true)
-                .possible_values(&WORKLOADS.keys().cloned().collect::<Vec<_>>()),
+                .multiple_occurrences(true)
+                .use_delimiter(true)
+                .possible_values(&WORKLOADS.keys().cloned().collect::<Vec<_>>())
+                .help("workloads to run, each table runs all of them"),
         )
         .arg(
             Arg::new("mutation_checker")
@@ -71,14 +305,135 @@ pub fn init_app() -> Config {
                 .long("status_addr")
                 .takes_value(true)
                 .required(false)
-                .default_value("127.0.0.1:10080"),
+                .multiple_occurrences(true)
+                .use_delimiter(true)
+                .default_value("127.0.0.1:10080")
+                .help("status addresses of all TiDB instances the workload may connect to"),
+        )
+        .arg(
+            Arg::new("tikv_status_addr")
+                .long("tikv_status_addr")
+                .takes_value(true)
+                .required(false)
+                .multiple_occurrences(true)
+                .use_delimiter(true)
+                .help("status addresses of TiKV instances, to run the TiKV injections. TiKV must be built with the corrupt_prewrite_mutation failpoint"),
+        )
+        .arg(
+            Arg::new("failpoint_timeout_ms")
+                .long("failpoint_timeout_ms")
+                .takes_value(true)
+                .required(false)
+                .default_value("5000")
+                .help("timeout of a failpoint request"),
+        )
+        .arg(
+            Arg::new("failpoint_retries")
+                .long("failpoint_retries")
+                .takes_value(true)
+                .required(false)
+                .default_value("3")
+                .help("retries of a failed failpoint request, with exponential backoff"),
         )
-        .get_matches();
+        .arg(
+            Arg::new("txn_size")
+                .long("txn_size")
+                .takes_value(true)
+                .required(false)
+                .default_value("2000")
+                .help("number of rows inserted and then updated by the large txn workload"),
+        )
+        .arg(
+            Arg::new("inject_at")
+                .long("inject_at")
+                .takes_value(true)
+                .required(false)
+                .default_value("0")
+                .help("index of the row write to corrupt in the large txn workload, the inserts come before the updates. It can't be given with --term"),
+        )
+        .arg(
+            Arg::new("commit_protocol")
+                .short('c')
+                .long("commit_protocol")
+                .takes_value(true)
+                .required(false)
+                .multiple_occurrences(true)
+                .use_delimiter(true)
+                .possible_values(CommitProtocol::NAMES)
+                .default_value("2pc"),
+        )
+        .arg(
+            Arg::new("protocol")
+                .short('p')
+                .long("protocol")
+                .takes_value(true)
+                .required(false)
+                .multiple_occurrences(true)
+                .use_delimiter(true)
+                .possible_values(StatementProtocol::NAMES)
+                .default_value("text"),
+        )
+        .arg(
+            Arg::new("set")
+                .long("set")
+                .takes_value(true)
+                .required(false)
+                .multiple_occurrences(true)
+                .value_name("name=v1,v2")
+                .help("set a system variable in the workload session, each value is a matrix dimension"),
+        )
+        .arg(
+            Arg::new("seed")
+                .long("seed")
+                .takes_value(true)
+                .required(false)
+                .help("seed of the fuzz workload, to replay a logged program"),
+        )
+        .arg(
+            Arg::new("shrink")
+                .long("shrink")
+                .takes_value(false)
+                .help("minimise failing cases, and write the reproducers to shrink_dir. Only the table is simplified, and also the DML sequence in the fuzz workload"),
+        )
+        .arg(
+            Arg::new("shrink_dir")
+                .long("shrink_dir")
+                .takes_value(true)
+                .required(false)
+                .default_value("shrink"),
+        )
+        .arg(
+            Arg::new("mvcc")
+                .long("mvcc")
+                .takes_value(false)
+                .help("when admin check table passes, check the keys of the table with the MVCC API of TiDB"),
+        )
+        .arg(
+            Arg::new("term")
+                .long("term")
+                .takes_value(true)
+                .required(false)
+                .multiple_occurrences(true)
+                .use_delimiter(true)
+                .value_name("term")
+                .validator(validate_term)
+                .help("failpoint term like 2*off->1*return, each term is a matrix dimension"),
+        )
+        .subcommand(
+            case_args(App::new("repro"))
+                .about("print a bash script that reproduces a case with the mysql client, the failpoint is not enabled while recording it"),
+        )
+        .subcommand(
+            case_args(App::new("replay"))
+                .about("run a single case with verbose logging, and print its classification"),
+        )
+        .get_matches_from(args);
     let config = Config {
-        workload_name: matches
-            .value_of("workload")
+        workloads: matches
+            .values_of("workload")
             .expect("must specify the workload parameter")
-            .to_lowercase(),
+            .map(str::to_lowercase)
+            .collect(),
         mutation_checker: matches.value_of("mutation_checker").unwrap().to_owned(),
         assertion: matches.value_of("assertion").unwrap().to_owned(),
         limit: matches
@@ -87,8 +442,98 @@ pub fn init_app() -> Config {
             .parse::<u32>()
             .expect("limit must be a non-negative number"),
         uri: matches.value_of("uri").unwrap().to_owned(),
-        status_addr: matches.value_of("status_addr").unwrap().to_owned(),
+        status_addrs: matches
+            .values_of("status_addr")
+            .unwrap()
+            .map(ToOwned::to_owned)
+            .collect(),
+        failpoint_timeout_ms: matches
+            .value_of("failpoint_timeout_ms")
+            .unwrap()
+            .parse::<u64>()
+            .expect("failpoint_timeout_ms must be a non-negative number"),
+        failpoint_retries: matches
+            .value_of("failpoint_retries")
+            .unwrap()
+            .parse::<u32>()
+            .expect("failpoint_retries must be a non-negative number"),
+        tikv_status_addrs: matches
+            .values_of("tikv_status_addr")
+            .map(|values| values.map(ToOwned::to_owned).collect())
+            .unwrap_or_default(),
         log_path: matches.value_of("log_path").unwrap().to_owned(),
+        txn_size: matches
+            .value_of("txn_size")
+            .unwrap()
+            .parse::<u32>()
+            .expect("txn_size must be a non-negative number"),
+        inject_at: matches
+            .value_of("inject_at")
+            .unwrap()
+            .parse::<u32>()
+            .expect("inject_at must be a non-negative number"),
+        commit_protocols: matches
+            .values_of("commit_protocol")
+            .unwrap()
+            .map(CommitProtocol::from_name)
+            .collect(),
+        protocols: matches
+            .values_of("protocol")
+            .unwrap()
+            .map(StatementProtocol::from_name)
+            .collect(),
+        vars: matches
+            .values_of("set")
+            .map(|values| {
+                values
+                    .map(|v| {
+                        let (name, values) = v
+                            .split_once('=')
+                            .expect("--set must be in the form of name=v1,v2");
+                        (
+                            name.trim().to_owned(),
+                            values.split(',').map(|x| x.trim().to_owned()).collect(),
+                        )
+                    })
+                    .collect()
+            })
+            .unwrap_or_default(),
+        seed: matches.value_of("seed").map(|s| {
+            s.parse::<u64>()
+                .expect("seed must be a non-negative number")
+        }),
+        shrink: matches.is_present("shrink"),
+        shrink_dir: matches.value_of("shrink_dir").unwrap().to_owned(),
+        mvcc: matches.is_present("mvcc"),
+        terms: matches
+            .values_of("term")
+            .map(|values| values.map(|v| Term::Custom(v.to_owned())).collect())
+            .unwrap_or_else(|| vec![Term::Default]),
+        command: match matches.subcommand() {
+            Some(("repro", m)) => Command::Repro {
+                table: m.value_of("table").unwrap().to_owned(),
+                injection: m.value_of("injection").unwrap().to_owned(),
+            },
+            Some(("replay", m)) => Command::Replay {
+                table: m.value_of("table").unwrap().to_owned(),
+                injection: m.value_of("injection").unwrap().to_owned(),
+            },
+            _ => Command::Run,
+        },
     };
+    // the large txn workload only skips row writes with its default term
+    assert!(
+        matches.occurrences_of("inject_at") == 0 || !matches.is_present("term"),
+        "inject_at can't be given with term, skip row writes in the term instead, e.g. 5*off->1*return"
+    );
+    assert!(
+        config.inject_at < 2 * config.txn_size,
+        "inject_at must be less than the rows written, i.e. 2 * txn_size"
+    );
+    assert!(
+        config.txn_size as usize <= DISTINCT_VALUES,
+        "txn_size must be at most {}, or the values don't fit in the columns",
+        DISTINCT_VALUES
+    );
     config
 }
@@ -19,4 +19,15 @@ pub enum MyError {
     },
     #[error("{0}")]
     Io(#[from] std::io::Error),
+    #[error("status server {status_addr} is unreachable: {reqwest}")]
+    FailpointUnreachable {
+        status_addr: String,
+        reqwest: reqwest::Error,
+    },
+    #[error("failpoint {name} is rejected with {status}: {text}")]
+    FailpointRejected {
+        name: String,
+        status: u16,
+        text: String,
+    },
 }
@@ -1,11 +1,36 @@
+use crate::error::MyError;
+use crate::repro::record_failpoint;
 use crate::Result;
 use crate::FAILPOINT_DURATION_MS;
+use lazy_static::lazy_static;
+use regex::Regex;
 use reqwest;
-use slog::error;
-use slog::Logger;
-use std::sync::atomic::Ordering;
+use slog::{error, warn, Logger};
+use sqlx::{Executor, MySqlConnection, Row};
+use std::collections::HashSet;
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
 use tokio::time;
 
+// the retry policy of failpoint requests, see `set_policy`
+static TIMEOUT_MS: AtomicU64 = AtomicU64::new(5000);
+static RETRIES: AtomicU32 = AtomicU32::new(3);
+// the first backoff, it doubles after each retry
+const BACKOFF_MS: u64 = 100;
+
+pub fn set_policy(timeout_ms: u64, retries: u32) {
+    TIMEOUT_MS.store(timeout_ms, Ordering::SeqCst);
+    RETRIES.store(retries, Ordering::SeqCst);
+}
+
+// a client whose requests time out by the policy
+pub fn new_client() -> Result<reqwest::Client> {
+    Ok(reqwest::Client::builder()
+        .timeout(Duration::from_millis(TIMEOUT_MS.load(Ordering::SeqCst)))
+        .build()?)
+}
+
 pub async fn enable_failpoint(
     log: &Logger,
     client: &reqwest::Client,
@@ -13,25 +38,15 @@ pub async fn enable_failpoint(
     name: impl Into<String>,
     value: impl Into<String>,
 ) -> Result<()> {
-    let start = time::Instant::now();
-    let res = client
-        .put(format!(
-            "http://{}/fail/{}",
-            status_addr.into(),
-            name.into()
-        ))
-        .body(value.into())
-        .send()
-        .await?;
-    let duration = start.elapsed();
-    FAILPOINT_DURATION_MS.fetch_add(duration.as_millis() as u64, Ordering::SeqCst);
-    if let Err(e) = res.error_for_status_ref() {
-        let status = res.status().as_u16();
-        let text = res.text().await?;
-        error!(log, "failed to enable failpoint"; "status code" => status, "text" => &text);
-        return Err(e.into());
+    let (status_addr, name, value) = (status_addr.into(), name.into(), value.into());
+    if record_failpoint(&status_addr, &name, Some(&value)) {
+        return Ok(());
     }
-    Ok(())
+    let url = format!("http://{}/fail/{}", status_addr, name);
+    send_with_retry(log, &status_addr, &name, || {
+        client.put(&url).body(value.clone())
+    })
+    .await
 }
 
 pub async fn disable_failpoint(
@@ -40,22 +55,379 @@ pub async fn disable_failpoint(
     status_addr: impl Into<String>,
     name: impl Into<String>,
 ) -> Result<()> {
-    let start = time::Instant::now();
-    let res = client
-        .delete(format!(
-            "http://{}/fail/{}",
-            status_addr.into(),
-            name.into()
-        ))
-        .send()
-        .await?;
-    let duration = start.elapsed();
-    FAILPOINT_DURATION_MS.fetch_add(duration.as_millis() as u64, Ordering::SeqCst);
-    if let Err(e) = res.error_for_status_ref() {
-        let status = res.status().as_u16();
-        let text = res.text().await?;
-        error!(log, "failed to disable failpoint"; "status code" => status, "text" => &text);
-        return Err(e.into());
+    let (status_addr, name) = (status_addr.into(), name.into());
+    if record_failpoint(&status_addr, &name, None) {
+        return Ok(());
+    }
+    let url = format!("http://{}/fail/{}", status_addr, name);
+    send_with_retry(log, &status_addr, &name, || client.delete(&url)).await
+}
+
+// Sends a failpoint request. Both enabling and disabling are idempotent, so they are retried with
+// backoff if the server can't be reached or fails. A rejected request is not retried.
+async fn send_with_retry(
+    log: &Logger,
+    status_addr: &str,
+    name: &str,
+    request: impl Fn() -> reqwest::RequestBuilder,
+) -> Result<()> {
+    let retries = RETRIES.load(Ordering::SeqCst);
+    let mut backoff = Duration::from_millis(BACKOFF_MS);
+    let mut attempt = 0;
+    loop {
+        let start = time::Instant::now();
+        let res = request().send().await;
+        let duration = start.elapsed();
+        FAILPOINT_DURATION_MS.fetch_add(duration.as_millis() as u64, Ordering::SeqCst);
+        let e = match res {
+            Ok(res) if res.status().is_success() => return Ok(()),
+            Ok(res) => {
+                let status = res.status();
+                let text = res.text().await.unwrap_or_default();
+                error!(log, "failpoint request failed"; "name" => name, "status code" => status.as_u16(), "text" => &text);
+                let e = MyError::FailpointRejected {
+                    name: name.to_owned(),
+                    status: status.as_u16(),
+                    text,
+                };
+                if status.is_client_error() {
+                    return Err(e);
+                }
+                e
+            }
+            Err(e) => MyError::FailpointUnreachable {
+                status_addr: status_addr.to_owned(),
+                reqwest: e,
+            },
+        };
+        if attempt >= retries {
+            return Err(e);
+        }
+        attempt += 1;
+        warn!(log, "retrying failpoint request"; "name" => name, "attempt" => attempt, "error" => %e);
+        time::sleep(backoff).await;
+        backoff *= 2;
+    }
+}
+
+// set by `disable_all`, no failpoint is enabled after it
+static CLOSED: AtomicBool = AtomicBool::new(false);
+
+// failpoints that are enabled and not disabled yet, as (status address, name)
+lazy_static! {
+    static ref ACTIVE: Mutex<HashSet<(String, String)>> = Mutex::new(HashSet::new());
+    // ddl ids of the TiDB instances whose failpoints are toggled, see `load_instances`
+    static ref INSTANCES: Mutex<HashSet<String>> = Mutex::new(HashSet::new());
+}
+
+// A failpoint on one or more instances. It's disabled by `disable`, or by `Case::run` after an
+// early return of a workload. Only if a panic unwinds past them, it's disabled when the guard is
+// dropped, by blocking.
+pub struct FailpointGuard {
+    log: Logger,
+    status_addrs: Vec<String>,
+    name: String,
+}
+
+impl FailpointGuard {
+    pub fn new(log: &Logger, status_addrs: &[String], name: impl Into<String>) -> Self {
+        FailpointGuard {
+            log: log.clone(),
+            status_addrs: status_addrs.to_vec(),
+            name: name.into(),
+        }
+    }
+
+    // enable the failpoint on all instances, or none of them if any fails. It fails once
+    // `disable_all` is called.
+    pub async fn enable(&self, client: &reqwest::Client, value: impl Into<String>) -> Result<()> {
+        let value = value.into();
+        for (i, status_addr) in self.status_addrs.iter().enumerate() {
+            {
+                let mut active = ACTIVE.lock().unwrap();
+                if CLOSED.load(Ordering::SeqCst) {
+                    return Err(MyError::StringError(format!(
+                        "failpoint {} is not enabled, all failpoints are disabled on exit",
+                        self.name
+                    )));
+                }
+                // registered before sending, in case the request is applied but its response is
+                // lost
+                active.insert((status_addr.clone(), self.name.clone()));
+            }
+            let res = enable_failpoint(&self.log, client, status_addr, &self.name, &value).await;
+            if let Err(e) = res {
+                for status_addr in &self.status_addrs[..=i] {
+                    self.disable_one(client, status_addr).await.ok();
+                }
+                return Err(e);
+            }
+        }
+        // `disable_all` may have run while the requests were on the way, and missed them
+        if CLOSED.load(Ordering::SeqCst) {
+            for status_addr in &self.status_addrs {
+                disable_failpoint(&self.log, client, status_addr, &self.name)
+                    .await
+                    .ok();
+            }
+            return Err(MyError::StringError(format!(
+                "failpoint {} is disabled on exit",
+                self.name
+            )));
+        }
+        Ok(())
+    }
+
+    // it skips the instances where the failpoint is not enabled, or already disabled by
+    // `disable_all`
+    pub async fn disable(&self, client: &reqwest::Client) -> Result<()> {
+        let mut res = Ok(());
+        for status_addr in &self.status_addrs {
+            if let Err(e) = self.disable_one(client, status_addr).await {
+                res = Err(e);
+            }
+        }
+        res
+    }
+
+    async fn disable_one(&self, client: &reqwest::Client, status_addr: &str) -> Result<()> {
+        if !ACTIVE
+            .lock()
+            .unwrap()
+            .remove(&(status_addr.to_owned(), self.name.clone()))
+        {
+            return Ok(());
+        }
+        disable_failpoint(&self.log, client, status_addr, &self.name).await
+    }
+}
+
+impl Drop for FailpointGuard {
+    fn drop(&mut self) {
+        if !std::thread::panicking() {
+            return;
+        }
+        let keys = {
+            let mut active = ACTIVE.lock().unwrap();
+            self.status_addrs
+                .iter()
+                .map(|status_addr| (status_addr.clone(), self.name.clone()))
+                .filter(|key| active.remove(key))
+                .collect::<Vec<_>>()
+        };
+        if keys.is_empty() {
+            return;
+        }
+        // the blocking client can't be used in the async runtime, send it from another thread
+        let errors = std::thread::spawn(move || {
+            keys.iter()
+                .filter_map(|(status_addr, name)| disable_blocking(status_addr, name).err())
+                .collect::<Vec<_>>()
+        })
+        .join()
+        .expect("failed to disable failpoint");
+        for e in errors {
+            error!(self.log, "failed to disable failpoint"; "name" => &self.name, "error" => ?e);
+        }
     }
+}
+
+// Disables every active failpoint, and refuses to enable any more. It blocks, and must not be
+// called in the async runtime. It's used by the ctrl-c handler.
+pub fn disable_all() {
+    let active = {
+        let mut active = ACTIVE.lock().unwrap();
+        CLOSED.store(true, Ordering::SeqCst);
+        std::mem::take(&mut *active)
+    };
+    for (status_addr, name) in active {
+        if let Err(e) = disable_blocking(&status_addr, &name) {
+            eprintln!("failed to disable failpoint {}: {:?}", name, e);
+        }
+    }
+}
+
+// the blocking version of `disable_failpoint`, with the same retry policy
+fn disable_blocking(status_addr: &str, name: &str) -> reqwest::Result<()> {
+    let client = reqwest::blocking::Client::builder()
+        .timeout(Duration::from_millis(TIMEOUT_MS.load(Ordering::SeqCst)))
+        .build()?;
+    let url = format!("http://{}/fail/{}", status_addr, name);
+    let mut backoff = Duration::from_millis(BACKOFF_MS);
+    let mut attempt = 0;
+    loop {
+        let res = client
+            .delete(&url)
+            .send()
+            .and_then(|r| r.error_for_status());
+        match res {
+            Ok(_) => return Ok(()),
+            Err(e) if e.status().is_some_and(|s| s.is_client_error()) => return Err(e),
+            Err(e) if attempt >= RETRIES.load(Ordering::SeqCst) => return Err(e),
+            Err(_) => {
+                attempt += 1;
+                std::thread::sleep(backoff);
+                backoff *= 2;
+            }
+        }
+    }
+}
+
+// Remembers the TiDB instances behind the status addresses, by their ddl ids.
+pub async fn load_instances(client: &reqwest::Client, status_addrs: &[String]) -> Result<()> {
+    let re = Regex::new(r#""ddl_id":\s*"([^"]+)""#).unwrap();
+    let mut instances = HashSet::new();
+    for status_addr in status_addrs {
+        let info = client
+            .get(format!("http://{}/info", status_addr))
+            .send()
+            .await?
+            .error_for_status()?
+            .text()
+            .await?;
+        let captures = re.captures(&info).ok_or_else(|| {
+            MyError::StringError(format!("no ddl_id in the info of {}", status_addr))
+        })?;
+        instances.insert(captures[1].to_owned());
+    }
+    *INSTANCES.lock().unwrap() = instances;
     Ok(())
 }
+
+// Checks that the connection is served by one of the instances whose failpoints are toggled.
+// Behind a load balancer it may land on another one, where nothing would be injected.
+pub async fn check_instance(conn: &mut MySqlConnection) -> Result<()> {
+    let row = conn.fetch_one("admin show ddl").await?;
+    let id: String = row.try_get("SELF_ID")?;
+    let instances = INSTANCES.lock().unwrap();
+    if !instances.is_empty() && !instances.contains(&id) {
+        return Err(MyError::StringError(format!(
+            "the connection is served by TiDB {}, which is not in --status_addr",
+            id
+        )));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::{new_client, FailpointGuard};
+    use crate::error::MyError;
+    use crate::mock::{Call, MockStatusServer};
+    use slog::{o, Discard, Logger};
+
+    const NAME: &str = "github.com/pingcap/tidb/table/tables/corruptMutations";
+
+    fn enable(value: &str) -> Call {
+        Call::Enable {
+            name: NAME.to_owned(),
+            value: value.to_owned(),
+        }
+    }
+
+    fn disable() -> Call {
+        Call::Disable {
+            name: NAME.to_owned(),
+        }
+    }
+
+    #[tokio::test]
+    async fn toggle_on_all_instances() {
+        let log = Logger::root(Discard, o!());
+        let client = new_client().unwrap();
+        let servers = [
+            MockStatusServer::start().await.unwrap(),
+            MockStatusServer::start().await.unwrap(),
+        ];
+        let addrs = servers.iter().map(|s| s.addr.clone()).collect::<Vec<_>>();
+        let guard = FailpointGuard::new(&log, &addrs, NAME);
+        guard
+            .enable(&client, "1*return(\"extraIndex\")")
+            .await
+            .unwrap();
+        for server in &servers {
+            assert_eq!(server.enabled().len(), 1);
+        }
+        guard.disable(&client).await.unwrap();
+        // disabling twice doesn't send anything
+        guard.disable(&client).await.unwrap();
+        for server in &servers {
+            assert_eq!(
+                server.calls(),
+                vec![enable("1*return(\"extraIndex\")"), disable()]
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn roll_back_if_any_instance_rejects() {
+        let log = Logger::root(Discard, o!());
+        let client = new_client().unwrap();
+        let servers = [
+            MockStatusServer::start().await.unwrap(),
+            MockStatusServer::start().await.unwrap(),
+        ];
+        servers[1].fail_next(400);
+        let addrs = servers.iter().map(|s| s.addr.clone()).collect::<Vec<_>>();
+        let guard = FailpointGuard::new(&log, &addrs, NAME);
+        let res = guard.enable(&client, "return(\"missingIndex\")").await;
+        assert!(matches!(
+            res,
+            Err(MyError::FailpointRejected { status: 400, .. })
+        ));
+        for server in &servers {
+            assert!(server.enabled().is_empty());
+        }
+        assert_eq!(
+            servers[0].calls(),
+            vec![enable("return(\"missingIndex\")"), disable()]
+        );
+    }
+
+    #[tokio::test]
+    async fn retry_on_server_errors() {
+        let log = Logger::root(Discard, o!());
+        let client = new_client().unwrap();
+        let server = MockStatusServer::start().await.unwrap();
+        server.fail_next(500);
+        let guard = FailpointGuard::new(&log, std::slice::from_ref(&server.addr), NAME);
+        guard
+            .enable(&client, "1*return(\"corruptIndexKey\")")
+            .await
+            .unwrap();
+        guard.disable(&client).await.unwrap();
+        assert_eq!(
+            server.calls(),
+            vec![
+                enable("1*return(\"corruptIndexKey\")"),
+                enable("1*return(\"corruptIndexKey\")"),
+                disable()
+            ]
+        );
+    }
+
+    // the blocking request on drop needs another worker to run the server
+    #[tokio::test(flavor = "multi_thread")]
+    async fn disable_on_panic() {
+        let log = Logger::root(Discard, o!());
+        let client = new_client().unwrap();
+        let server = MockStatusServer::start().await.unwrap();
+        let guard = FailpointGuard::new(&log, std::slice::from_ref(&server.addr), NAME);
+        guard
+            .enable(&client, "1*return(\"corruptIndexValue\")")
+            .await
+            .unwrap();
+        // dropped while the thread unwinds, as a workload panics
+        let res = std::thread::spawn(move || {
+            let _guard = guard;
+            panic!("the workload panics");
+        })
+        .join();
+        assert!(res.is_err());
+        assert!(server.enabled().is_empty());
+        assert_eq!(
+            server.calls(),
+            vec![enable("1*return(\"corruptIndexValue\")"), disable()]
+        );
+    }
+}
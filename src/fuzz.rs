@@ -0,0 +1,169 @@
+use crate::table::{Dml, Table};
+
+// number of distinct rows a program works on. It's small so that statements often touch the same
+// rows and conflict with each other.
+const KEY_SPACE: usize = 4;
+const MIN_STEPS: usize = 5;
+const MAX_STEPS: usize = 20;
+
+// splitmix64. A seed must replay exactly the same program, regardless of versions of any crate.
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Rng(seed)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    // a number in [0, n)
+    pub fn below(&mut self, n: usize) -> usize {
+        (self.next_u64() % n as u64) as usize
+    }
+}
+
+// a step of a program. Rows are referred to by keys, the key `k` is `table.new_row().nth(k)`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Step {
+    Begin { pessimistic: bool },
+    Commit,
+    Rollback,
+    Insert(usize),
+    Replace(usize),
+    // set the first column of row `.0` to the one of row `.1`
+    Update(usize, usize),
+    Delete(usize),
+}
+
+impl Step {
+    // the txn control statement, or `None` if it's a DML
+    pub fn control(&self) -> Option<&'static str> {
+        match self {
+            Step::Begin { pessimistic: false } => Some("begin optimistic"),
+            Step::Begin { pessimistic: true } => Some("begin pessimistic"),
+            Step::Commit => Some("commit"),
+            Step::Rollback => Some("rollback"),
+            _ => None,
+        }
+    }
+
+    pub fn dml(&self, table: &Table) -> Option<Dml> {
+        let row = table.new_row();
+        match *self {
+            Step::Insert(k) => Some(table.insert(&[row.nth(k)])),
+            Step::Replace(k) => Some(table.replace(&[row.nth(k)])),
+            Step::Update(k, v) => {
+                Some(table.update((0, row.cols[0].nth(v)), (1, row.cols[1].nth(k))))
+            }
+            Step::Delete(k) => Some(table.delete((1, row.cols[1].nth(k)))),
+            _ => None,
+        }
+    }
+
+    fn is_dml(&self) -> bool {
+        self.control().is_none()
+    }
+}
+
+// a random sequence of DML in random txn boundaries, with the injection enabled right before
+// the `inject_at`-th step, which is always a DML.
+#[derive(Clone, Debug)]
+pub struct Program {
+    pub seed: u64,
+    pub steps: Vec<Step>,
+    pub inject_at: usize,
+}
+
+impl Program {
+    pub fn generate(seed: u64) -> Self {
+        let mut rng = Rng::new(seed);
+        let len = MIN_STEPS + rng.below(MAX_STEPS - MIN_STEPS + 1);
+        let mut steps = Vec::with_capacity(len + 2);
+        let mut in_txn = false;
+        while steps.len() < len {
+            if !in_txn && rng.below(3) == 0 {
+                steps.push(Step::Begin {
+                    pessimistic: rng.below(2) == 0,
+                });
+                in_txn = true;
+                continue;
+            }
+            if in_txn && rng.below(4) == 0 {
+                steps.push(if rng.below(3) == 0 {
+                    Step::Rollback
+                } else {
+                    Step::Commit
+                });
+                in_txn = false;
+                continue;
+            }
+            let k = rng.below(KEY_SPACE);
+            steps.push(match rng.below(4) {
+                0 => Step::Insert(k),
+                1 => Step::Replace(k),
+                2 => Step::Update(k, rng.below(KEY_SPACE)),
+                _ => Step::Delete(k),
+            });
+        }
+        if in_txn {
+            steps.push(Step::Commit);
+        }
+        if !steps.iter().any(Step::is_dml) {
+            steps.push(Step::Insert(rng.below(KEY_SPACE)));
+        }
+        let dmls = steps
+            .iter()
+            .enumerate()
+            .filter(|(_, s)| s.is_dml())
+            .map(|(i, _)| i)
+            .collect::<Vec<_>>();
+        let inject_at = dmls[rng.below(dmls.len())];
+        Program {
+            seed,
+            steps,
+            inject_at,
+        }
+    }
+
+    // programs with one step less, the injected step is always kept
+    pub fn shorter(&self) -> Vec<Program> {
+        (0..self.steps.len())
+            .filter(|&i| i != self.inject_at)
+            .map(|i| {
+                let mut steps = self.steps.clone();
+                steps.remove(i);
+                Program {
+                    seed: self.seed,
+                    steps,
+                    inject_at: if i < self.inject_at {
+                        self.inject_at - 1
+                    } else {
+                        self.inject_at
+                    },
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Program;
+
+    #[test]
+    fn replay_from_seed() {
+        for seed in 0..100 {
+            let a = Program::generate(seed);
+            let b = Program::generate(seed);
+            assert_eq!(a.steps, b.steps);
+            assert_eq!(a.inject_at, b.inject_at);
+            assert!(a.steps[a.inject_at].control().is_none());
+        }
+    }
+}
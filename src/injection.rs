@@ -0,0 +1,180 @@
+use crate::config::{split_term, Config, Term};
+use crate::table::Table;
+use std::fmt;
+
+// where a failpoint lives
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Target {
+    TiDB,
+    TiKV,
+}
+
+// A way to corrupt the data written by a txn, by enabling a failpoint in TiDB or TiKV.
+// Assume that injections won't corrupt table_ids. Otherwise drop table may not be able to clear
+// corrupted data, and will affect following tests.
+#[derive(Clone, Copy, Debug)]
+pub struct Injection {
+    // the name in results and logs
+    pub name: &'static str,
+    pub target: Target,
+    pub failpoint: &'static str,
+    // the action of the failpoint term, it replaces every `return` in a term
+    action: &'static str,
+    // whether the injection makes sense on the table
+    applicable: fn(&Table) -> bool,
+    // whether the features under test are expected to detect it
+    detected_by_mutation_checker: bool,
+    detected_by_assertion: bool,
+}
+
+const CORRUPT_MUTATIONS: &str = "github.com/pingcap/tidb/table/tables/corruptMutations";
+// TiKV doesn't ship a failpoint that corrupts data, it needs a build with this one in the prewrite
+// of a mutation
+const CORRUPT_PREWRITE: &str = "corrupt_prewrite_mutation";
+
+pub static INJECTIONS: &[Injection] = &[
+    Injection {
+        name: "extraIndex",
+        target: Target::TiDB,
+        failpoint: CORRUPT_MUTATIONS,
+        action: "return(\"extraIndex\")",
+        applicable: Table::has_secondary_index,
+        detected_by_mutation_checker: true,
+        detected_by_assertion: true,
+    },
+    Injection {
+        name: "missingIndex",
+        target: Target::TiDB,
+        failpoint: CORRUPT_MUTATIONS,
+        action: "return(\"missingIndex\")",
+        applicable: Table::has_secondary_index,
+        detected_by_mutation_checker: true,
+        detected_by_assertion: true,
+    },
+    Injection {
+        name: "corruptIndexKey",
+        target: Target::TiDB,
+        failpoint: CORRUPT_MUTATIONS,
+        action: "return(\"corruptIndexKey\")",
+        applicable: Table::has_secondary_index,
+        detected_by_mutation_checker: true,
+        detected_by_assertion: true,
+    },
+    // the keys are intact, only the value differs from the row
+    Injection {
+        name: "corruptIndexValue",
+        target: Target::TiDB,
+        failpoint: CORRUPT_MUTATIONS,
+        action: "return(\"corruptIndexValue\")",
+        applicable: Table::has_secondary_index,
+        detected_by_mutation_checker: true,
+        detected_by_assertion: false,
+    },
+    // TiKV acknowledges the prewrite of an index key without writing it. The mutations are intact
+    // in TiDB, so only reading the data back can find it.
+    Injection {
+        name: "dropPrewrite",
+        target: Target::TiKV,
+        failpoint: CORRUPT_PREWRITE,
+        action: "return(drop)",
+        applicable: Table::has_secondary_index,
+        detected_by_mutation_checker: false,
+        detected_by_assertion: false,
+    },
+    // TiKV writes a different value for an index key in prewrite
+    Injection {
+        name: "alterPrewrite",
+        target: Target::TiKV,
+        failpoint: CORRUPT_PREWRITE,
+        action: "return(alter)",
+        applicable: Table::has_secondary_index,
+        detected_by_mutation_checker: false,
+        detected_by_assertion: false,
+    },
+];
+
+impl Injection {
+    pub fn find(name: &str) -> Option<&'static Injection> {
+        INJECTIONS.iter().find(|i| i.name == name)
+    }
+
+    pub fn names() -> Vec<&'static str> {
+        INJECTIONS.iter().map(|i| i.name).collect()
+    }
+
+    // the injections that make sense on the table
+    pub fn applicable_to(config: &Config, table: &Table) -> Vec<Injection> {
+        INJECTIONS
+            .iter()
+            .filter(|i| i.is_applicable(config, table))
+            .copied()
+            .collect()
+    }
+
+    pub fn is_applicable(&self, config: &Config, table: &Table) -> bool {
+        self.is_enabled(config) && (self.applicable)(table)
+    }
+
+    // TiKV injections are only run when TiKV status addresses are given
+    pub fn is_enabled(&self, config: &Config) -> bool {
+        !self.status_addrs(config).is_empty()
+    }
+
+    // status addresses of the instances the failpoint is toggled on
+    pub fn status_addrs<'a>(&self, config: &'a Config) -> &'a [String] {
+        match self.target {
+            Target::TiDB => &config.status_addrs,
+            Target::TiKV => &config.tikv_status_addrs,
+        }
+    }
+
+    // the failpoint value with the term, or with `default` if it's the default term. Every
+    // `return` action of it is replaced by the injection.
+    pub fn value(&self, term: &Term, default: &str) -> String {
+        split_term(term.template(default))
+            .into_iter()
+            .map(|(prefix, action)| match action {
+                "return" => format!("{}{}", prefix, self.action),
+                _ => format!("{}{}", prefix, action),
+            })
+            .collect::<Vec<_>>()
+            .join("->")
+    }
+
+    // whether the features under test are expected to detect the injection
+    pub fn expected(&self, config: &Config) -> bool {
+        let mutation_checker =
+            ["1", "true", "on"].contains(&config.mutation_checker.to_lowercase().as_str());
+        let assertion = !config.assertion.eq_ignore_ascii_case("off");
+        (mutation_checker && self.detected_by_mutation_checker)
+            || (assertion && self.detected_by_assertion)
+    }
+}
+
+impl fmt::Display for Injection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Injection;
+    use crate::config::Term;
+
+    #[test]
+    fn value_replaces_every_return() {
+        let injection = Injection::find("missingIndex").unwrap();
+        assert_eq!(
+            injection.value(
+                &Term::Custom("1*return->2*off->50%return".to_owned()),
+                "return"
+            ),
+            "1*return(\"missingIndex\")->2*off->50%return(\"missingIndex\")"
+        );
+        assert_eq!(
+            injection.value(&Term::Default, "3*off->1*return"),
+            "3*off->1*return(\"missingIndex\")"
+        );
+    }
+}
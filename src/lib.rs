@@ -1,28 +1,44 @@
 #![feature(backtrace)]
 
-// assume that injections won't corrupt table_ids. Otherwise drop table may not be able to clear corrupted data,
-// and will affect following tests.
-pub const AVAILABLE_INJECTIONS: &[&str] = &[
-    "extraIndex",
-    "missingIndex",
-    "corruptIndexKey",
-    "corruptIndexValue",
-];
-
 pub mod config;
 pub mod error;
 pub mod failpoint;
+pub mod fuzz;
+pub mod injection;
 pub mod metrics;
+#[cfg(any(test, feature = "mock"))]
+pub mod mock;
+pub mod mvcc;
+pub mod repro;
+pub mod shrink;
 pub mod table;
 pub mod workload;
 
 pub use metrics::*;
 
+#[derive(Debug)]
 pub enum Effectiveness {
-    Success,    // the error message contains "inconsist"-like words
-    OtherError, // other errors are reported
-    Failure,    // failed to detect error
-    Consistent, // the injections don't affect - e.g. `admin check table` returns no error
+    Success,                 // the error message contains "inconsist"-like words
+    ReadSuccess, // the writes succeeded, but reading the data back reports "inconsist"-like errors
+    OtherError,  // other errors are reported
+    Failure(Option<String>), // failed to detect error, with the index found inconsistent if any
+    Consistent,  // the injections don't affect - e.g. `admin check table` returns no error
+    Undetected,  // `admin check table` returns no error, but the MVCC API finds corrupted keys
+}
+
+#[derive(Debug)]
+pub struct CaseResult {
+    pub effectiveness: Effectiveness,
+    // (succeeded, total) jobs of a non-transactional DML, as TiDB reports them
+    pub jobs: Option<(usize, usize)>,
+    // rows written by the txn of the large txn workload
+    pub txn_size: Option<usize>,
 }
 
 pub type Result<T> = std::result::Result<T, error::MyError>;
+
+// {table} x {workload} x {injection} x {session} x {term} -> result
+pub type Results = std::collections::HashMap<
+    (table::Table, String, String, config::Session, config::Term),
+    CaseResult,
+>;
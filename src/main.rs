@@ -1,16 +1,22 @@
 #[macro_use]
 extern crate prettytable;
 use corrupttest::{
-    config::{init_app, Config},
+    config::{init_app, Command, Config, Term},
+    error::MyError,
+    failpoint::{disable_all, load_instances, new_client, set_policy, FailpointGuard},
+    injection::{Injection, INJECTIONS},
+    repro::repro,
+    shrink::shrink,
     table::*,
-    workload::find_workload,
-    Effectiveness, Result, AVAILABLE_INJECTIONS, CREATE_TABLE_DURAION_MS, FAILPOINT_DURATION_MS,
+    workload::{find_workload, is_inconsistency_error, Case, Workload},
+    Effectiveness, Result, Results, CREATE_TABLE_DURAION_MS, FAILPOINT_DURATION_MS,
 };
 use futures::{pin_mut, StreamExt};
-use slog::{info, o, Drain, Logger};
+use slog::{error, info, o, warn, Drain, Logger};
 use sqlx::mysql::MySqlPoolOptions;
+use sqlx::Executor;
 use std::{
-    collections::HashMap,
+    collections::BTreeMap,
     sync::{
         atomic::{AtomicBool, Ordering},
         Arc,
@@ -24,20 +30,70 @@ static EXIT: AtomicBool = AtomicBool::new(false);
 async fn main() -> std::result::Result<(), Box<dyn std::error::Error>> {
     let config = init_app();
     let log = init_logger(&config);
-    let workload = find_workload(&config.workload_name);
+    let workloads = config
+        .workloads
+        .iter()
+        .map(|name| find_workload(name))
+        .collect::<Vec<_>>();
     let (client, pool) = init_pool(&log, &config).await?;
+    preflight(&log, &config, &client, &pool).await?;
     ctrlc::set_handler(move || {
         EXIT.store(true, Ordering::SeqCst);
+        // the current case runs to the end with no failpoint, and its result is discarded
+        disable_all();
     })?;
     info!(log, "initialized"; "config" => ?config);
 
+    match &config.command {
+        Command::Run => {}
+        Command::Repro { table, injection } => {
+            let table = find_table(table).await?;
+            let injection = find_injection(&config, injection)?;
+            for workload in &workloads {
+                for session in config.sessions() {
+                    for term in &config.terms {
+                        let case = Case {
+                            log: log.clone(),
+                            config: &config,
+                            session: &session,
+                            term,
+                            table: &table,
+                            injection,
+                            client: &client,
+                            pool: pool.clone(),
+                        };
+                        println!("{}", repro(&case, workload).await?);
+                    }
+                }
+            }
+            return Ok(());
+        }
+        Command::Replay { table, injection } => {
+            let table = find_table(table).await?;
+            let injection = find_injection(&config, injection)?;
+            for workload in &workloads {
+                replay(
+                    &log,
+                    &config,
+                    workload,
+                    &table,
+                    injection,
+                    &client,
+                    pool.clone(),
+                )
+                .await?;
+            }
+            return Ok(());
+        }
+    }
+
     let tables = Table::stream();
     pin_mut!(tables);
-    // {table} x {workload} x {injection} -> (success, other success, failure)
-    let mut results = HashMap::<(Table, String, String), Effectiveness>::new();
+    let sessions = config.sessions();
+    let mut results = Results::new();
     let mut cnt = 0;
     let start = time::Instant::now();
-    while let Some(table) = tables.next().await {
+    'tables: while let Some(table) = tables.next().await {
         if EXIT.load(Ordering::SeqCst) {
             break;
         }
@@ -45,16 +101,51 @@ async fn main() -> std::result::Result<(), Box<dyn std::error::Error>> {
             break;
         }
         cnt += 1;
-        workload
-            .execute(
-                log.clone(),
-                &config,
-                table,
-                &client,
-                pool.clone(),
-                &mut results,
-            )
-            .await?;
+        let injections = Injection::applicable_to(&config, &table);
+        for workload in &workloads {
+            for session in &sessions {
+                for term in &config.terms {
+                    for injection in &injections {
+                        if EXIT.load(Ordering::SeqCst) {
+                            break 'tables;
+                        }
+                        let case = Case {
+                            log: log.clone(),
+                            config: &config,
+                            session,
+                            term,
+                            table: &table,
+                            injection,
+                            client: &client,
+                            pool: pool.clone(),
+                        };
+                        let res = case.run(workload.execute(&case, &mut results)).await;
+                        if EXIT.load(Ordering::SeqCst) {
+                            results.remove(&case.key(workload.name()));
+                            warn!(log, "case is interrupted, its result is discarded"; "table" => &table.name, "workload" => workload.name(), "injection" => injection.name);
+                            break 'tables;
+                        }
+                        // e.g. the failpoint can't be enabled, the case has no result
+                        if let Err(e) = res {
+                            error!(log, "case failed"; "table" => &table.name, "workload" => workload.name(), "injection" => injection.name, "session" => %session, "term" => %term, "error" => %e);
+                            continue;
+                        }
+                        let failed = matches!(
+                            results
+                                .get(&case.key(workload.name()))
+                                .map(|r| &r.effectiveness),
+                            Some(Effectiveness::Failure(_) | Effectiveness::Undetected)
+                        );
+                        if config.shrink && failed {
+                            // the case keeps its result without a reproducer
+                            if let Err(e) = shrink(&case, workload).await {
+                                error!(log, "shrink failed"; "table" => &table.name, "workload" => workload.name(), "injection" => injection.name, "session" => %session, "term" => %term, "error" => %e);
+                            }
+                        }
+                    }
+                }
+            }
+        }
         info!(
             log,
             "stats";
@@ -66,11 +157,256 @@ async fn main() -> std::result::Result<(), Box<dyn std::error::Error>> {
     Ok(())
 }
 
+// the table the failpoint of the TiDB injections is tried on
+const TIDB_PROBE_TABLE: &str = "corrupttest_probe";
+// the table the failpoint of the TiKV injections is tried on
+const TIKV_PROBE_TABLE: &str = "corrupttest_tikv_probe";
+// the system variables that every workload sets
+const REQUIRED_VARIABLES: &[&str] = &[
+    "tidb_enable_mutation_checker",
+    "tidb_txn_assertion_level",
+    "tidb_enable_async_commit",
+    "tidb_enable_1pc",
+];
+
+// Checks that the server is a failpoint-enabled build and has the features under test, before
+// any table is created. Otherwise nothing is injected, and every case looks consistent.
+async fn preflight(
+    log: &Logger,
+    config: &Config,
+    client: &reqwest::Client,
+    pool: &sqlx::Pool<sqlx::MySql>,
+) -> Result<()> {
+    for status_addr in &config.status_addrs {
+        let res = client
+            .get(format!("http://{}/status", status_addr))
+            .send()
+            .await;
+        if !res.is_ok_and(|r| r.status().is_success()) {
+            return Err(MyError::StringError(format!(
+                "status API at {} is unreachable",
+                status_addr
+            )));
+        }
+        let list_url = format!("http://{}/fail/", status_addr);
+        if !client.get(&list_url).send().await?.status().is_success() {
+            return Err(MyError::StringError(format!(
+                "failpoint API at {} is unavailable, TiDB must be built with failpoints enabled",
+                status_addr
+            )));
+        }
+    }
+
+    for status_addr in &config.tikv_status_addrs {
+        let res = client
+            .get(format!("http://{}/status", status_addr))
+            .send()
+            .await;
+        if !res.is_ok_and(|r| r.status().is_success()) {
+            return Err(MyError::StringError(format!(
+                "TiKV status API at {} is unreachable",
+                status_addr
+            )));
+        }
+    }
+
+    probe_tidb(log, config, client, pool).await?;
+    load_instances(client, &config.status_addrs).await?;
+    if !config.tikv_status_addrs.is_empty() {
+        probe_tikv(log, config, client, pool).await?;
+    }
+
+    let vars = config.vars.iter().map(|(name, _)| name.as_str());
+    for name in REQUIRED_VARIABLES.iter().copied().chain(vars) {
+        let rows = pool
+            .fetch_all(format!("show variables like '{}'", name).as_str())
+            .await?;
+        if rows.is_empty() {
+            return Err(MyError::StringError(format!(
+                "system variable {} doesn't exist",
+                name
+            )));
+        }
+    }
+    info!(log, "preflight passed");
+    Ok(())
+}
+
+// The failpoint API of TiDB accepts any name, so the failpoint of the TiDB injections is only
+// known to be compiled in when the index entry it drops is caught, by the insertion or by
+// `admin check table`
+async fn probe_tidb(
+    log: &Logger,
+    config: &Config,
+    client: &reqwest::Client,
+    pool: &sqlx::Pool<sqlx::MySql>,
+) -> Result<()> {
+    let injection = Injection::find("missingIndex").unwrap();
+    pool.execute(format!("drop table if exists {}", TIDB_PROBE_TABLE).as_str())
+        .await?;
+    pool.execute(
+        format!(
+            "create table {} (id int primary key, v int, key i(v))",
+            TIDB_PROBE_TABLE
+        )
+        .as_str(),
+    )
+    .await?;
+    let failpoint = FailpointGuard::new(log, injection.status_addrs(config), injection.failpoint);
+    failpoint
+        .enable(client, &injection.value(&Term::Default, "1*return"))
+        .await?;
+    let res = pool
+        .execute(format!("insert into {} values (1, 1)", TIDB_PROBE_TABLE).as_str())
+        .await;
+    let fired = match res {
+        Err(e) if is_inconsistency_error(&e) => true,
+        Err(e) => {
+            failpoint.disable(client).await?;
+            return Err(e.into());
+        }
+        Ok(_) => pool
+            .execute(format!("admin check table {}", TIDB_PROBE_TABLE).as_str())
+            .await
+            .is_err(),
+    };
+    failpoint.disable(client).await?;
+    pool.execute(format!("drop table {}", TIDB_PROBE_TABLE).as_str())
+        .await?;
+    if !fired {
+        return Err(MyError::StringError(format!(
+            "failpoint {} doesn't fire in TiDB, it needs a TiDB build with failpoints enabled",
+            injection.failpoint
+        )));
+    }
+    Ok(())
+}
+
+// TiKV accepts any failpoint name, even one that isn't compiled in, so the failpoint of the TiKV
+// injections is only known to exist when an index key it drops is missed by `admin check table`
+async fn probe_tikv(
+    log: &Logger,
+    config: &Config,
+    client: &reqwest::Client,
+    pool: &sqlx::Pool<sqlx::MySql>,
+) -> Result<()> {
+    let injection = Injection::find("dropPrewrite").unwrap();
+    pool.execute(format!("drop table if exists {}", TIKV_PROBE_TABLE).as_str())
+        .await?;
+    pool.execute(
+        format!(
+            "create table {} (id int primary key, v int, key i(v))",
+            TIKV_PROBE_TABLE
+        )
+        .as_str(),
+    )
+    .await?;
+    let failpoint = FailpointGuard::new(log, &config.tikv_status_addrs, injection.failpoint);
+    failpoint
+        .enable(client, &injection.value(&Term::Default, "return"))
+        .await?;
+    let res = pool
+        .execute(format!("insert into {} values (1, 1)", TIKV_PROBE_TABLE).as_str())
+        .await;
+    failpoint.disable(client).await?;
+    res?;
+    let fired = pool
+        .execute(format!("admin check table {}", TIKV_PROBE_TABLE).as_str())
+        .await
+        .is_err();
+    pool.execute(format!("drop table {}", TIKV_PROBE_TABLE).as_str())
+        .await?;
+    if !fired {
+        return Err(MyError::StringError(format!(
+            "failpoint {} doesn't fire in TiKV, it needs a TiKV build with it",
+            injection.failpoint
+        )));
+    }
+    Ok(())
+}
+
+fn find_injection(config: &Config, name: &str) -> Result<&'static Injection> {
+    let injection = Injection::find(name).unwrap();
+    if !injection.is_enabled(config) {
+        return Err(MyError::StringError(format!(
+            "no status address is given for injection {}",
+            name
+        )));
+    }
+    Ok(injection)
+}
+
+async fn find_table(id: &str) -> Result<Table> {
+    Table::find(id)
+        .await
+        .ok_or_else(|| MyError::StringError(format!("table {} not found", id)))
+}
+
+// run a single case in every session, the statements are logged to stderr as they are sent
+async fn replay(
+    log: &Logger,
+    config: &Config,
+    workload: &Arc<dyn Workload>,
+    table: &Table,
+    injection: &Injection,
+    client: &reqwest::Client,
+    pool: Arc<sqlx::Pool<sqlx::MySql>>,
+) -> Result<()> {
+    println!("{}", table.create_statement());
+    for session in config.sessions() {
+        for term in &config.terms {
+            let case = Case {
+                log: log.clone(),
+                config,
+                session: &session,
+                term,
+                table,
+                injection,
+                client,
+                pool: pool.clone(),
+            };
+            let mut results = Results::new();
+            case.run(workload.execute(&case, &mut results)).await?;
+            for effectiveness in results.values().map(|r| &r.effectiveness) {
+                println!(
+                    "{} {} {}: {:?}",
+                    workload.name(),
+                    session,
+                    term,
+                    effectiveness
+                );
+            }
+            // the error of the workload is logged by the workload, check the table again for the
+            // inconsistency it left behind
+            match pool
+                .execute(format!("admin check table {}", table.name).as_str())
+                .await
+            {
+                Ok(_) => println!(
+                    "{} {} {}: admin check table passed",
+                    workload.name(),
+                    session,
+                    term
+                ),
+                Err(e) => println!(
+                    "{} {} {}: admin check table failed: {}",
+                    workload.name(),
+                    session,
+                    term,
+                    e
+                ),
+            }
+        }
+    }
+    Ok(())
+}
+
 async fn init_pool(
     log: &Logger,
     config: &Config,
 ) -> Result<(reqwest::Client, Arc<sqlx::Pool<sqlx::MySql>>)> {
-    let client = reqwest::Client::new();
+    set_policy(config.failpoint_timeout_ms, config.failpoint_retries);
+    let client = new_client()?;
     info!(log, "using tidb {}", config.uri);
     let pool = MySqlPoolOptions::new()
         .max_connections(32)
@@ -88,7 +424,11 @@ fn init_logger(config: &Config) -> Logger {
         .open(&config.log_path)
         .unwrap();
     let file_decorator = slog_term::PlainSyncDecorator::new(file);
-    // also print error logs to stderr
+    // also print error logs to stderr, or everything when replaying a single case
+    let level = match config.command {
+        Command::Replay { .. } => slog::Level::Info,
+        _ => slog::Level::Warning,
+    };
     let stderr_decorator = slog_term::TermDecorator::new().build();
     let file_drain = slog_term::FullFormat::new(file_decorator)
         .use_file_location()
@@ -98,7 +438,7 @@ fn init_logger(config: &Config) -> Logger {
             slog_term::FullFormat::new(stderr_decorator)
                 .use_file_location()
                 .build(),
-            slog::Level::Warning,
+            level,
         )
         .fuse(),
     )
@@ -107,45 +447,119 @@ fn init_logger(config: &Config) -> Logger {
     slog::Logger::root(drain, o!())
 }
 
-fn print_result(
-    log: Logger,
-    config: &Config,
-    cnt: u32,
-    results: HashMap<(Table, String, String), Effectiveness>,
-) {
+fn print_result(log: Logger, config: &Config, cnt: u32, results: Results) {
     info!(log, "printing result"; 
-        "workload" => &config.workload_name, 
+        "workloads" => ?config.workloads, 
         "total tables" => cnt, 
+        "txn size" => config.txn_size, 
         "DDL duration" => CREATE_TABLE_DURAION_MS.load(Ordering::SeqCst), 
         "failpoint duration" => FAILPOINT_DURATION_MS.load(Ordering::SeqCst));
     let mut table = prettytable::Table::new();
     table.add_row(row![
+        "workload",
+        "session",
+        "term",
         "injection",
         "success",
+        "read success",
         "other error",
         "failure",
         "consistent",
+        "undetected",
+        "succeeded jobs",
+        "txn size",
+        "inconsistent indices",
+        "expected",
     ]);
-    for &injection in AVAILABLE_INJECTIONS {
-        let counts = results
-            .iter()
-            .filter(|(key, _)| key.2.as_str() == injection)
-            .fold((0, 0, 0, 0), |acc, (_, value)| match value {
-                Effectiveness::Success => (acc.0 + 1, acc.1, acc.2, acc.3),
-                Effectiveness::OtherError => (acc.0, acc.1 + 1, acc.2, acc.3),
-                Effectiveness::Failure => (acc.0, acc.1, acc.2 + 1, acc.3),
-                Effectiveness::Consistent => (acc.0, acc.1, acc.2, acc.3 + 1),
-            });
-        info!(
-            log,
-            "{}:\tsuccess:{}\tother success:{}\tfailure:{}\tconsistent:{}",
-            injection,
-            counts.0,
-            counts.1,
-            counts.2,
-            counts.3
-        );
-        table.add_row(row![injection, counts.0, counts.1, counts.2, counts.3,]);
+    for workload in &config.workloads {
+        for session in config.sessions() {
+            for term in &config.terms {
+                for injection in INJECTIONS.iter().filter(|i| i.is_enabled(config)) {
+                    let values = results
+                        .iter()
+                        .filter(|(key, _)| {
+                            &key.1 == workload
+                                && key.2 == injection.name
+                                && key.3 == session
+                                && &key.4 == term
+                        })
+                        .map(|(_, value)| value)
+                        .collect::<Vec<_>>();
+                    let counts = values.iter().fold((0, 0, 0, 0, 0, 0), |acc, value| {
+                        match value.effectiveness {
+                            Effectiveness::Success => {
+                                (acc.0 + 1, acc.1, acc.2, acc.3, acc.4, acc.5)
+                            }
+                            Effectiveness::OtherError => {
+                                (acc.0, acc.1 + 1, acc.2, acc.3, acc.4, acc.5)
+                            }
+                            Effectiveness::Failure(_) => {
+                                (acc.0, acc.1, acc.2 + 1, acc.3, acc.4, acc.5)
+                            }
+                            Effectiveness::Consistent => {
+                                (acc.0, acc.1, acc.2, acc.3 + 1, acc.4, acc.5)
+                            }
+                            Effectiveness::ReadSuccess => {
+                                (acc.0, acc.1, acc.2, acc.3, acc.4 + 1, acc.5)
+                            }
+                            Effectiveness::Undetected => {
+                                (acc.0, acc.1, acc.2, acc.3, acc.4, acc.5 + 1)
+                            }
+                        }
+                    });
+                    // where the failures landed, as index:count
+                    let mut indices = BTreeMap::new();
+                    for value in &values {
+                        if let Effectiveness::Failure(Some(index)) = &value.effectiveness {
+                            *indices.entry(index.as_str()).or_insert(0) += 1;
+                        }
+                    }
+                    let indices = indices
+                        .iter()
+                        .map(|(index, n)| format!("{}:{}", index, n))
+                        .collect::<Vec<_>>()
+                        .join(",");
+                    // only non-transactional DML has jobs
+                    let jobs = values
+                        .iter()
+                        .filter_map(|value| value.jobs)
+                        .reduce(|a, b| (a.0 + b.0, a.1 + b.1))
+                        .map(|(succeeded, total)| format!("{}/{}", succeeded, total))
+                        .unwrap_or_default();
+                    // only the large txn workload has a txn size, the same in every case
+                    let txn_size = values
+                        .iter()
+                        .find_map(|value| value.txn_size)
+                        .map(|size| size.to_string())
+                        .unwrap_or_default();
+                    info!(
+                        log,
+                        "{}:\tsuccess:{}\tother success:{}\tfailure:{}\tconsistent:{}\tread success:{}\tsession:{}\tterm:{}\tundetected:{}\tworkload:{}\tsucceeded jobs:{}\ttxn size:{}\tinconsistent indices:{}",
+                        injection,
+                        counts.0,
+                        counts.1,
+                        counts.2,
+                        counts.3,
+                        counts.4,
+                        session,
+                        term,
+                        counts.5,
+                        workload,
+                        jobs,
+                        txn_size,
+                        indices
+                    );
+                    let expected = injection.expected(config);
+                    if expected && counts.2 + counts.5 > 0 {
+                        warn!(log, "expected injection is not detected"; "workload" => workload, "injection" => injection.name, "session" => %session, "term" => %term, "failure" => counts.2, "undetected" => counts.5);
+                    }
+                    table.add_row(row![
+                        workload, session, term, injection, counts.0, counts.4, counts.1, counts.2,
+                        counts.3, counts.5, jobs, txn_size, indices, expected,
+                    ]);
+                }
+            }
+        }
     }
     table.printstd();
 }
@@ -0,0 +1,533 @@
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+
+// a request to the failpoint API of `MockStatusServer`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Call {
+    Enable { name: String, value: String },
+    Disable { name: String },
+    Get { name: String }, // `name` is empty when listing all failpoints
+}
+
+#[derive(Default)]
+struct State {
+    calls: Vec<Call>,
+    enabled: BTreeMap<String, String>,
+    // status codes to answer the following failpoint requests with
+    failures: VecDeque<u16>,
+    // path -> (status, body), for the other APIs
+    routes: HashMap<String, (u16, String)>,
+}
+
+// A stand-in for the status server of TiDB, for tests. It serves `/status`, `/info` and
+// `/fail/{name}` like a failpoint-enabled build, records the failpoint requests, and can be told
+// to fail them. Other paths are answered by `route`.
+#[derive(Clone)]
+pub struct MockStatusServer {
+    pub addr: String,
+    state: Arc<Mutex<State>>,
+}
+
+impl MockStatusServer {
+    pub async fn start() -> std::io::Result<Self> {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let addr = listener.local_addr()?.to_string();
+        let state = Arc::new(Mutex::new(State::default()));
+        let server_state = state.clone();
+        let ddl_id = format!("mock-{}", addr);
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let state = server_state.clone();
+                let ddl_id = ddl_id.clone();
+                tokio::spawn(async move { serve(stream, &state, &ddl_id).await.ok() });
+            }
+        });
+        Ok(MockStatusServer { addr, state })
+    }
+
+    // the ddl id that `/info` reports
+    pub fn ddl_id(&self) -> String {
+        format!("mock-{}", self.addr)
+    }
+
+    pub fn calls(&self) -> Vec<Call> {
+        self.state.lock().unwrap().calls.clone()
+    }
+
+    // failpoints that are enabled, as name -> value
+    pub fn enabled(&self) -> BTreeMap<String, String> {
+        self.state.lock().unwrap().enabled.clone()
+    }
+
+    // answer GET requests of `path` with `body`, whatever the query is
+    pub fn route(&self, path: &str, status: u16, body: &str) {
+        self.state
+            .lock()
+            .unwrap()
+            .routes
+            .insert(path.to_owned(), (status, body.to_owned()));
+    }
+
+    // answer the next failpoint request with `status` instead of serving it. Calls queue up.
+    pub fn fail_next(&self, status: u16) {
+        self.state.lock().unwrap().failures.push_back(status);
+    }
+}
+
+async fn serve(stream: TcpStream, state: &Mutex<State>, ddl_id: &str) -> std::io::Result<()> {
+    let mut reader = BufReader::new(stream);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line).await?;
+    let mut content_length = 0;
+    loop {
+        let mut line = String::new();
+        reader.read_line(&mut line).await?;
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            if name.eq_ignore_ascii_case("content-length") {
+                content_length = value.trim().parse().unwrap_or(0);
+            }
+        }
+    }
+    let mut body = vec![0; content_length];
+    reader.read_exact(&mut body).await?;
+    let body = String::from_utf8_lossy(&body).into_owned();
+
+    let mut parts = request_line.split_whitespace();
+    let method = parts.next().unwrap_or_default();
+    let path = parts.next().unwrap_or_default();
+    let (status, text) = respond(state, ddl_id, method, path, body);
+    let response = format!(
+        "HTTP/1.1 {} MOCK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        text.len(),
+        text
+    );
+    reader.get_mut().write_all(response.as_bytes()).await?;
+    reader.get_mut().shutdown().await
+}
+
+fn respond(
+    state: &Mutex<State>,
+    ddl_id: &str,
+    method: &str,
+    path: &str,
+    body: String,
+) -> (u16, String) {
+    let mut state = state.lock().unwrap();
+    let path = path.split('?').next().unwrap_or_default();
+    if let Some((status, body)) = state.routes.get(path).filter(|_| method == "GET") {
+        return (*status, body.clone());
+    }
+    let name = match path.strip_prefix("/fail/") {
+        Some(name) => name.to_owned(),
+        None => {
+            return match (method, path) {
+                ("GET", "/status") => (200, r#"{"version":"mock"}"#.to_owned()),
+                ("GET", "/info") => (200, format!(r#"{{"ddl_id": "{}"}}"#, ddl_id)),
+                _ => (404, "not found".to_owned()),
+            };
+        }
+    };
+    let call = match method {
+        "PUT" => Call::Enable {
+            name: name.clone(),
+            value: body.clone(),
+        },
+        "DELETE" => Call::Disable { name: name.clone() },
+        "GET" => Call::Get { name: name.clone() },
+        _ => return (405, "method not allowed".to_owned()),
+    };
+    state.calls.push(call);
+    if let Some(status) = state.failures.pop_front() {
+        return (status, "injected failure".to_owned());
+    }
+    match method {
+        "PUT" => {
+            state.enabled.insert(name, body);
+            (200, String::new())
+        }
+        // like pingcap/failpoint, disabling a failpoint that is not enabled is an error
+        "DELETE" => match state.enabled.remove(&name) {
+            Some(_) => (200, String::new()),
+            None => (400, "failpoint: failpoint is disabled".to_owned()),
+        },
+        _ if name.is_empty() => (
+            200,
+            state
+                .enabled
+                .iter()
+                .map(|(name, value)| format!("{}={}\n", name, value))
+                .collect(),
+        ),
+        _ => match state.enabled.get(&name) {
+            Some(value) => (200, value.clone()),
+            None => (404, "failpoint: failpoint is disabled".to_owned()),
+        },
+    }
+}
+
+// the reply of `MockMySqlServer` to a statement
+#[derive(Clone, Debug)]
+pub enum Reply {
+    Ok {
+        affected_rows: u64,
+    },
+    Rows {
+        columns: Vec<String>,
+        rows: Vec<Vec<Option<String>>>,
+    },
+    Error {
+        code: u16,
+        message: String,
+    },
+}
+
+impl Reply {
+    pub fn ok() -> Self {
+        Reply::Ok { affected_rows: 0 }
+    }
+
+    pub fn rows(columns: &[&str], rows: Vec<Vec<Option<String>>>) -> Self {
+        Reply::Rows {
+            columns: columns.iter().map(|&c| c.to_owned()).collect(),
+            rows,
+        }
+    }
+
+    // ErrAssertionFailed of TiDB
+    pub fn assertion_failed() -> Self {
+        Reply::Error {
+            code: 8141,
+            message:
+                "assertion failed: key: 7480000000000000465f728000000000000001, \
+                      assertion: NotExist, start_ts: 1, existing start ts: 1, existing commit ts: 2"
+                    .to_owned(),
+        }
+    }
+
+    // ErrInconsistentIndexedValue of TiDB, returned by the mutation checker
+    pub fn inconsistent_index(table: &str) -> Self {
+        Reply::Error {
+            code: 8140,
+            message: format!(
+                "writing inconsistent data in table: {}, index: i1, col: c1, \
+                 indexed-value:{{KindInt64 1}} != record-value:{{KindInt64 2}}",
+                table
+            ),
+        }
+    }
+
+    // ErrDataInconsistent of TiDB, returned by `admin check table`
+    pub fn data_inconsistent(table: &str) -> Self {
+        Reply::Error {
+            code: 8133,
+            message: format!(
+                "data inconsistency in table: {}, index: i1, handle: 1, \
+                 index-values:\"\" != record-values:\"handle: 1, values: [KindInt64 1]\"",
+                table
+            ),
+        }
+    }
+}
+
+type Rule = Box<dyn Fn(&str) -> Option<Reply> + Send + Sync>;
+
+#[derive(Default)]
+struct Script {
+    statements: Vec<String>,
+    rules: Vec<Rule>,
+}
+
+impl Script {
+    // the first rule that answers the statement wins, the rest are answered like TiDB with an
+    // empty database
+    fn reply(&mut self, sql: &str, ddl_id: &str) -> Reply {
+        self.statements.push(sql.to_owned());
+        if let Some(reply) = self.rules.iter().find_map(|rule| rule(sql)) {
+            return reply;
+        }
+        let lower = sql.trim().to_lowercase();
+        if let Some(name) = lower.strip_prefix("show variables like '") {
+            let name = name.trim_end_matches('\'').to_owned();
+            Reply::rows(&["Variable_name", "Value"], vec![vec![Some(name), None]])
+        } else if lower == "admin show ddl" {
+            let id = Some(ddl_id.to_owned());
+            Reply::rows(&["OWNER_ID", "SELF_ID"], vec![vec![id.clone(), id]])
+        } else if lower.starts_with("select") {
+            Reply::rows(&["c1"], vec![])
+        } else {
+            Reply::ok()
+        }
+    }
+}
+
+// A stand-in for the MySQL protocol of TiDB, for tests. It answers every statement by the rules
+// given to `on`, and records them. Statements of the binary protocol are answered by their
+// prepared form, without the parameters.
+pub struct MockMySqlServer {
+    pub addr: String,
+    script: Arc<Mutex<Script>>,
+}
+
+impl MockMySqlServer {
+    // `ddl_id` is the id of the instance that `admin show ddl` reports, see
+    // `MockStatusServer::ddl_id`
+    pub async fn start(ddl_id: String) -> std::io::Result<Self> {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let addr = listener.local_addr()?.to_string();
+        let script = Arc::new(Mutex::new(Script::default()));
+        let server_script = script.clone();
+        tokio::spawn(async move {
+            let mut connection_id = 0;
+            while let Ok((stream, _)) = listener.accept().await {
+                connection_id += 1;
+                let script = server_script.clone();
+                let ddl_id = ddl_id.clone();
+                tokio::spawn(async move {
+                    serve_mysql(stream, connection_id, &script, &ddl_id)
+                        .await
+                        .ok()
+                });
+            }
+        });
+        Ok(MockMySqlServer { addr, script })
+    }
+
+    pub fn uri(&self) -> String {
+        format!("mysql://root@{}/test", self.addr)
+    }
+
+    // Add a rule, which answers a statement by returning `Some`. Rules are tried in the order
+    // they are added.
+    pub fn on(&self, rule: impl Fn(&str) -> Option<Reply> + Send + Sync + 'static) {
+        self.script.lock().unwrap().rules.push(Box::new(rule));
+    }
+
+    pub fn statements(&self) -> Vec<String> {
+        self.script.lock().unwrap().statements.clone()
+    }
+}
+
+const COM_QUIT: u8 = 0x01;
+const COM_QUERY: u8 = 0x03;
+const COM_PING: u8 = 0x0e;
+const COM_STMT_PREPARE: u8 = 0x16;
+const COM_STMT_EXECUTE: u8 = 0x17;
+const COM_STMT_CLOSE: u8 = 0x19;
+
+// CLIENT_LONG_PASSWORD | CLIENT_CONNECT_WITH_DB | CLIENT_PROTOCOL_41 | CLIENT_TRANSACTIONS |
+// CLIENT_SECURE_CONNECTION | CLIENT_MULTI_RESULTS | CLIENT_PLUGIN_AUTH | CLIENT_DEPRECATE_EOF
+const CAPABILITIES: u32 = 1 | 8 | 512 | 8192 | (1 << 15) | (1 << 17) | (1 << 19) | (1 << 24);
+const SERVER_STATUS_AUTOCOMMIT: u16 = 2;
+const UTF8MB4_UNICODE_CI: u16 = 224;
+const MYSQL_TYPE_VAR_STRING: u8 = 0xfd;
+
+// the packets of a response, with the sequence ids following the request
+struct Response {
+    buf: Vec<u8>,
+    seq: u8,
+}
+
+impl Response {
+    fn new(seq: u8) -> Self {
+        Response { buf: vec![], seq }
+    }
+
+    fn packet(&mut self, payload: &[u8]) {
+        self.buf
+            .extend_from_slice(&(payload.len() as u32).to_le_bytes()[..3]);
+        self.buf.push(self.seq);
+        self.buf.extend_from_slice(payload);
+        self.seq = self.seq.wrapping_add(1);
+    }
+
+    fn ok(&mut self, affected_rows: u64) {
+        let mut p = vec![0x00];
+        put_lenenc_int(&mut p, affected_rows);
+        put_lenenc_int(&mut p, 0);
+        p.extend_from_slice(&SERVER_STATUS_AUTOCOMMIT.to_le_bytes());
+        p.extend_from_slice(&0u16.to_le_bytes());
+        self.packet(&p);
+    }
+
+    // the OK packet that ends a result set, in place of EOF
+    fn eof(&mut self) {
+        let mut p = vec![0xfe, 0, 0];
+        p.extend_from_slice(&SERVER_STATUS_AUTOCOMMIT.to_le_bytes());
+        p.extend_from_slice(&0u16.to_le_bytes());
+        self.packet(&p);
+    }
+
+    fn error(&mut self, code: u16, message: &str) {
+        let mut p = vec![0xff];
+        p.extend_from_slice(&code.to_le_bytes());
+        p.extend_from_slice(b"#HY000");
+        p.extend_from_slice(message.as_bytes());
+        self.packet(&p);
+    }
+
+    fn column(&mut self, name: &str) {
+        let mut p = vec![];
+        for s in ["def", "test", "", "", name, name] {
+            put_lenenc_str(&mut p, s);
+        }
+        p.push(0x0c);
+        p.extend_from_slice(&UTF8MB4_UNICODE_CI.to_le_bytes());
+        p.extend_from_slice(&1024u32.to_le_bytes());
+        p.push(MYSQL_TYPE_VAR_STRING);
+        p.extend_from_slice(&0u16.to_le_bytes()); // flags
+        p.extend_from_slice(&[0, 0, 0]); // decimals and filler
+        self.packet(&p);
+    }
+
+    fn reply(&mut self, reply: &Reply) {
+        match reply {
+            Reply::Ok { affected_rows } => self.ok(*affected_rows),
+            Reply::Error { code, message } => self.error(*code, message),
+            Reply::Rows { columns, rows } => {
+                let mut p = vec![];
+                put_lenenc_int(&mut p, columns.len() as u64);
+                self.packet(&p);
+                for column in columns {
+                    self.column(column);
+                }
+                for row in rows {
+                    let mut p = vec![];
+                    for value in row {
+                        match value {
+                            Some(value) => put_lenenc_str(&mut p, value),
+                            None => p.push(0xfb),
+                        }
+                    }
+                    self.packet(&p);
+                }
+                self.eof();
+            }
+        }
+    }
+}
+
+fn put_lenenc_int(buf: &mut Vec<u8>, x: u64) {
+    match x {
+        0..=250 => buf.push(x as u8),
+        251..=0xffff => {
+            buf.push(0xfc);
+            buf.extend_from_slice(&(x as u16).to_le_bytes());
+        }
+        0x10000..=0xffffff => {
+            buf.push(0xfd);
+            buf.extend_from_slice(&(x as u32).to_le_bytes()[..3]);
+        }
+        _ => {
+            buf.push(0xfe);
+            buf.extend_from_slice(&x.to_le_bytes());
+        }
+    }
+}
+
+fn put_lenenc_str(buf: &mut Vec<u8>, s: &str) {
+    put_lenenc_int(buf, s.len() as u64);
+    buf.extend_from_slice(s.as_bytes());
+}
+
+async fn read_packet(stream: &mut TcpStream) -> std::io::Result<(u8, Vec<u8>)> {
+    let mut header = [0; 4];
+    stream.read_exact(&mut header).await?;
+    let len = u32::from_le_bytes([header[0], header[1], header[2], 0]) as usize;
+    let mut payload = vec![0; len];
+    stream.read_exact(&mut payload).await?;
+    Ok((header[3], payload))
+}
+
+fn handshake(connection_id: u32) -> Vec<u8> {
+    let mut p = vec![10];
+    p.extend_from_slice(b"5.7.25-TiDB-mock\0");
+    p.extend_from_slice(&connection_id.to_le_bytes());
+    p.extend_from_slice(b"01234567\0");
+    p.extend_from_slice(&(CAPABILITIES as u16).to_le_bytes());
+    p.push(UTF8MB4_UNICODE_CI as u8);
+    p.extend_from_slice(&SERVER_STATUS_AUTOCOMMIT.to_le_bytes());
+    p.extend_from_slice(&((CAPABILITIES >> 16) as u16).to_le_bytes());
+    p.push(21); // length of the nonce with the NUL
+    p.extend_from_slice(&[0; 10]);
+    p.extend_from_slice(b"89abcdefghij\0");
+    p.extend_from_slice(b"mysql_native_password\0");
+    p
+}
+
+// Serves a connection. Any password is accepted.
+async fn serve_mysql(
+    mut stream: TcpStream,
+    connection_id: u32,
+    script: &Mutex<Script>,
+    ddl_id: &str,
+) -> std::io::Result<()> {
+    let mut response = Response::new(0);
+    response.packet(&handshake(connection_id));
+    stream.write_all(&response.buf).await?;
+    let (seq, _) = read_packet(&mut stream).await?;
+    let mut response = Response::new(seq.wrapping_add(1));
+    response.ok(0);
+    stream.write_all(&response.buf).await?;
+
+    let mut prepared = HashMap::new();
+    let mut statement_id = 0u32;
+    loop {
+        let (seq, payload) = read_packet(&mut stream).await?;
+        let mut response = Response::new(seq.wrapping_add(1));
+        let (&command, body) = payload.split_first().unwrap_or((&COM_QUIT, &[]));
+        match command {
+            COM_QUIT => return Ok(()),
+            COM_PING => response.ok(0),
+            COM_QUERY => {
+                let sql = String::from_utf8_lossy(body);
+                let reply = script.lock().unwrap().reply(&sql, ddl_id);
+                response.reply(&reply);
+            }
+            COM_STMT_PREPARE => {
+                let sql = String::from_utf8_lossy(body).into_owned();
+                statement_id += 1;
+                let id = statement_id;
+                let params = sql.matches('?').count() as u16;
+                let mut p = vec![0x00];
+                p.extend_from_slice(&id.to_le_bytes());
+                p.extend_from_slice(&0u16.to_le_bytes()); // columns
+                p.extend_from_slice(&params.to_le_bytes());
+                p.extend_from_slice(&[0, 0, 0]); // filler and warnings
+                response.packet(&p);
+                for _ in 0..params {
+                    response.column("?");
+                }
+                prepared.insert(id, sql);
+            }
+            COM_STMT_EXECUTE => {
+                let id = u32::from_le_bytes(body[..4].try_into().unwrap());
+                let reply = match prepared.get(&id) {
+                    Some(sql) => script.lock().unwrap().reply(sql, ddl_id),
+                    None => Reply::Error {
+                        code: 1243,
+                        message: format!("Unknown prepared statement handler ({})", id),
+                    },
+                };
+                match reply {
+                    Reply::Rows { .. } => {
+                        response.error(1105, "the mock doesn't return rows in the binary protocol")
+                    }
+                    reply => response.reply(&reply),
+                }
+            }
+            COM_STMT_CLOSE => {
+                prepared.remove(&u32::from_le_bytes(body[..4].try_into().unwrap()));
+                continue;
+            }
+            _ => response.error(1047, "Unknown command"),
+        }
+        stream.write_all(&response.buf).await?;
+    }
+}
@@ -0,0 +1,440 @@
+use crate::error::MyError;
+use crate::table::Table;
+use crate::Result;
+use serde::Deserialize;
+use sqlx::{Executor, MySql, Pool, Row as _};
+
+// the response of `/mvcc/key` and `/mvcc/index`, only the parts that tell whether the key exists
+// and what its value is
+#[derive(Deserialize)]
+struct MvccKv {
+    // in upper case hex
+    #[serde(default)]
+    key: String,
+    value: Option<MvccValue>,
+}
+
+#[derive(Deserialize)]
+struct MvccValue {
+    info: Option<MvccInfo>,
+}
+
+#[derive(Deserialize)]
+struct MvccInfo {
+    #[serde(default)]
+    writes: Vec<MvccWrite>,
+    // the values that are too long to be kept in their writes
+    #[serde(default)]
+    values: Vec<MvccLongValue>,
+}
+
+#[derive(Deserialize)]
+struct MvccWrite {
+    // kvrpcpb.Op, it's omitted for Put
+    #[serde(rename = "type", default)]
+    op: i32,
+    #[serde(default)]
+    start_ts: u64,
+    // base64, omitted if it's empty or kept in `MvccInfo::values`
+    short_value: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct MvccLongValue {
+    #[serde(default)]
+    start_ts: u64,
+    // base64
+    value: Option<String>,
+}
+
+const OP_PUT: i32 = 0;
+const OP_DEL: i32 = 1;
+
+impl MvccKv {
+    // Writes are listed from the latest. Locks and rollbacks are skipped, they don't change the
+    // value.
+    fn latest_put(&self) -> Option<&MvccWrite> {
+        self.value
+            .as_ref()
+            .and_then(|v| v.info.as_ref())
+            .and_then(|info| {
+                info.writes
+                    .iter()
+                    .find(|w| w.op == OP_PUT || w.op == OP_DEL)
+            })
+            .filter(|w| w.op == OP_PUT)
+    }
+
+    fn exists(&self) -> bool {
+        self.latest_put().is_some()
+    }
+
+    // the value of the key, which must exist
+    fn bytes(&self) -> Result<Vec<u8>> {
+        let write = self.latest_put().unwrap();
+        let encoded = write.short_value.as_ref().or_else(|| {
+            self.value
+                .as_ref()
+                .and_then(|v| v.info.as_ref())
+                .and_then(|info| info.values.iter().find(|v| v.start_ts == write.start_ts))
+                .and_then(|v| v.value.as_ref())
+        });
+        match encoded {
+            Some(encoded) => base64::decode(encoded)
+                .map_err(|e| MyError::StringError(format!("bad value {}: {}", encoded, e))),
+            None => Ok(vec![]),
+        }
+    }
+}
+
+async fn fetch_kv(
+    client: &reqwest::Client,
+    status_addr: &str,
+    path: &str,
+    params: &[(String, String)],
+) -> Result<MvccKv> {
+    Ok(client
+        .get(format!("http://{}{}", status_addr, path))
+        .query(params)
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?)
+}
+
+// what a row is keyed by, an int or the encoded primary key
+#[derive(Debug, PartialEq, Eq)]
+enum Handle {
+    Int(i64),
+    Common(Vec<u8>),
+}
+
+// `t`, the table id and `_r`, before the handle in a record key
+const RECORD_PREFIX_LEN: usize = 11;
+// the options in the index values since TiDB 5.0, each led by its flag, see `tablecodec`
+const INT_HANDLE_FLAG: u8 = 124;
+const INDEX_VERSION_FLAG: u8 = 125;
+const PARTITION_ID_FLAG: u8 = 126;
+const COMMON_HANDLE_FLAG: u8 = 127;
+// values before TiDB 5.0 are no longer than the int handle and the untouched flag
+const MAX_OLD_ENCODE_VALUE_LEN: usize = 9;
+
+// the handle at the end of a record key in hex. An int handle is encoded with its sign bit flipped.
+fn record_handle(key: &str, int_handle: bool) -> Option<Handle> {
+    let key = (0..key.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(key.get(i..i + 2)?, 16).ok())
+        .collect::<Option<Vec<u8>>>()?;
+    if key.len() <= RECORD_PREFIX_LEN || key[0] != b't' || &key[9..11] != b"_r" {
+        return None;
+    }
+    let handle = &key[RECORD_PREFIX_LEN..];
+    if !int_handle {
+        return Some(Handle::Common(handle.to_vec()));
+    }
+    let handle = u64::from_be_bytes(handle.try_into().ok()?) ^ (1 << 63);
+    Some(Handle::Int(handle as i64))
+}
+
+// Decodes the handle in the value of a unique index. Before TiDB 5.0, the value is the int handle,
+// followed by the untouched flag if the txn didn't change the row. Since then, the first byte is
+// the length of the tail, and the options come before restored data and the tail. The handle is
+// an option, except that an int handle leads the tail in the first version of the encoding.
+fn decode_index_value(value: &[u8]) -> std::result::Result<Handle, String> {
+    let be_int = |bytes: &[u8]| {
+        bytes
+            .get(..8)
+            .map(|b| Handle::Int(i64::from_be_bytes(b.try_into().unwrap())))
+            .ok_or_else(|| "the int handle is cut short".to_owned())
+    };
+    if value.len() <= MAX_OLD_ENCODE_VALUE_LEN {
+        return be_int(value);
+    }
+    let tail_len = value[0] as usize;
+    let mut options = value
+        .get(1..value.len().saturating_sub(tail_len))
+        .ok_or_else(|| format!("the tail of {} bytes is longer than the value", tail_len))?;
+    let mut versioned = false;
+    loop {
+        let (flag, len) = match options.first() {
+            Some(&INDEX_VERSION_FLAG) => (INDEX_VERSION_FLAG, 2),
+            Some(&PARTITION_ID_FLAG) => (PARTITION_ID_FLAG, 9),
+            Some(&INT_HANDLE_FLAG) => return be_int(&options[1..]),
+            Some(&COMMON_HANDLE_FLAG) => {
+                let len = options
+                    .get(1..3)
+                    .map(|l| u16::from_be_bytes(l.try_into().unwrap()) as usize)
+                    .ok_or("the common handle is cut short")?;
+                return options
+                    .get(3..3 + len)
+                    .map(|h| Handle::Common(h.to_vec()))
+                    .ok_or_else(|| "the common handle is cut short".to_owned());
+            }
+            // restored data, or no options
+            _ => break,
+        };
+        versioned |= flag == INDEX_VERSION_FLAG;
+        options = options.get(len..).ok_or("an option is cut short")?;
+    }
+    if versioned || tail_len < 8 {
+        return Err("no handle".to_owned());
+    }
+    be_int(&value[value.len() - tail_len..])
+}
+
+// Looks for the corruption that a case left in storage, through the MVCC API of TiDB at
+// `status_addr`. Every row must have a key in each secondary index, and no index may have more
+// entries than the rows. Extra index keys can't be addressed from the rows, so they are counted
+// instead. The value of a unique index must hold the handle of its row, other indices keep the
+// handle in their keys, and the rest of their values is not compared. Returns a description of
+// the first corruption found.
+pub async fn find_corruption(
+    client: &reqwest::Client,
+    status_addr: &str,
+    table: &Table,
+    pool: &Pool<MySql>,
+) -> Result<Option<String>> {
+    let mut conn = pool.acquire().await?;
+    let db: String = conn.fetch_one("SELECT DATABASE()").await?.try_get(0)?;
+    let rows = conn.fetch_all(table.scan_statement().as_str()).await?;
+    let clustered = table.clustered_columns();
+    let indices = table.secondary_indices();
+    let record_path = format!("/mvcc/key/{}/{}", db, table.name);
+    for row in &rows {
+        let values = (0..row.len())
+            .map(|i| row.try_get(i))
+            .collect::<std::result::Result<Vec<Option<String>>, _>>()?;
+        // NULL can't be given to the API, such rows are left to `admin check table`
+        let values = match values.into_iter().collect::<Option<Vec<String>>>() {
+            Some(values) => values,
+            None => continue,
+        };
+        let (handle, values) = match clustered {
+            Some(_) => (None, &values[..]),
+            None => (Some(&values[0]), &values[1..]),
+        };
+        let params = |names: &[String]| {
+            names
+                .iter()
+                .map(|name| {
+                    let pos = table.cols.iter().position(|c| &c.name == name).unwrap();
+                    (name.clone(), values[pos].clone())
+                })
+                .collect::<Vec<_>>()
+        };
+
+        // the row is read by the table scan, so its key must be found, or the API is misused
+        let (path, query) = match (&clustered, handle) {
+            (Some(pk), _) => (record_path.clone(), params(pk)),
+            (None, handle) => (format!("{}/{}", record_path, handle.unwrap()), vec![]),
+        };
+        let record = fetch_kv(client, status_addr, &path, &query).await?;
+        if !record.exists() {
+            return Err(MyError::StringError(format!(
+                "MVCC API at {} doesn't find the row ({}) of table {}",
+                status_addr,
+                values.join(", "),
+                table.name
+            )));
+        }
+        let row_handle = record_handle(&record.key, table.has_int_handle()).ok_or_else(|| {
+            MyError::StringError(format!(
+                "MVCC API at {} returns the row ({}) of table {} with a bad key {}",
+                status_addr,
+                values.join(", "),
+                table.name,
+                record.key
+            ))
+        })?;
+
+        for (index, columns) in &indices {
+            let index_path = format!("/mvcc/index/{}/{}/{}", db, table.name, index);
+            let (path, query) = match (&clustered, handle) {
+                (Some(pk), _) => {
+                    let mut names = columns.clone();
+                    names.extend(pk.iter().filter(|c| !columns.contains(c)).cloned());
+                    (index_path, params(&names))
+                }
+                (None, handle) => (
+                    format!("{}/{}", index_path, handle.unwrap()),
+                    params(columns),
+                ),
+            };
+            let entry = fetch_kv(client, status_addr, &path, &query).await?;
+            if !entry.exists() {
+                return Ok(Some(format!(
+                    "index {} misses the row ({})",
+                    index,
+                    values.join(", ")
+                )));
+            }
+            if !table.is_unique(index) {
+                continue;
+            }
+            match decode_index_value(&entry.bytes()?) {
+                Ok(handle) if handle == row_handle => {}
+                Ok(handle) => {
+                    return Ok(Some(format!(
+                        "index {} points the row ({}) to {:?} instead of {:?}",
+                        index,
+                        values.join(", "),
+                        handle,
+                        row_handle
+                    )))
+                }
+                Err(e) => {
+                    return Ok(Some(format!(
+                        "index {} has a bad value for the row ({}): {}",
+                        index,
+                        values.join(", "),
+                        e
+                    )))
+                }
+            }
+        }
+    }
+
+    for (index, _) in &indices {
+        let count: String = conn
+            .fetch_one(
+                format!(
+                    "SELECT CAST(COUNT(*) AS CHAR) FROM {} USE INDEX({})",
+                    table.name, index
+                )
+                .as_str(),
+            )
+            .await?
+            .try_get(0)?;
+        let count = count.parse::<usize>().unwrap_or_default();
+        if count > rows.len() {
+            return Ok(Some(format!(
+                "index {} has {} entries for {} rows",
+                index,
+                count,
+                rows.len()
+            )));
+        }
+    }
+    Ok(None)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{decode_index_value, find_corruption, Handle};
+    use crate::mock::{MockMySqlServer, MockStatusServer, Reply};
+    use crate::table::Table;
+    use sqlx::mysql::MySqlPoolOptions;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    // the row of handle 1 in the table of id 100
+    const ROW: &str = r#"{"key": "7480000000000000645F728000000000000001", "region_id": 2, "value": {"info": {"writes": [{"start_ts": 2, "commit_ts": 3, "short_value": "gAAB"}]}}}"#;
+    const PUT: &str = r#"{"key": "", "region_id": 2, "value": {"info": {"writes": [{"start_ts": 2, "commit_ts": 3, "short_value": "gAAB"}]}}}"#;
+    const DELETED: &str = r#"{"key": "", "region_id": 2, "value": {"info": {"writes": [{"type": 3, "start_ts": 5, "commit_ts": 5}, {"type": 1, "start_ts": 4, "commit_ts": 5}, {"start_ts": 2, "commit_ts": 3}]}}}"#;
+    const ABSENT: &str = r#"{"key": "", "region_id": 2, "value": {"info": {}}}"#;
+
+    #[tokio::test]
+    async fn find_missing_and_extra_keys() {
+        let status = MockStatusServer::start().await.unwrap();
+        let mysql = MockMySqlServer::start(status.ddl_id()).await.unwrap();
+        let entries = Arc::new(AtomicUsize::new(2));
+        let index_entries = entries.clone();
+        mysql.on(move |sql| match sql {
+            "SELECT DATABASE()" => Some(Reply::rows(&["db"], vec![vec![Some("test".into())]])),
+            _ if sql.starts_with("SELECT CAST(_tidb_rowid") => Some(Reply::rows(
+                &["h", "c1", "c2"],
+                vec![vec![Some("1".into()), Some("10".into()), Some("20".into())]],
+            )),
+            _ if sql.starts_with("SELECT CAST(COUNT(*)") => {
+                let count = index_entries.load(Ordering::SeqCst).to_string();
+                Some(Reply::rows(&["count"], vec![vec![Some(count)]]))
+            }
+            _ => None,
+        });
+        let client = reqwest::Client::new();
+        let pool = MySqlPoolOptions::new().connect(&mysql.uri()).await.unwrap();
+        let table =
+            Table::from_create_statement("CREATE TABLE t (c1 INT, c2 INT, KEY i1 (c1))").unwrap();
+
+        status.route("/mvcc/key/test/t/1", 200, ROW);
+        status.route("/mvcc/index/test/t/i1/1", 200, DELETED);
+        let found = find_corruption(&client, &status.addr, &table, &pool).await;
+        assert_eq!(found.unwrap().unwrap(), "index i1 misses the row (10, 20)");
+
+        // the key is there, but the index has an entry of no row
+        status.route("/mvcc/index/test/t/i1/1", 200, PUT);
+        let found = find_corruption(&client, &status.addr, &table, &pool).await;
+        assert_eq!(found.unwrap().unwrap(), "index i1 has 2 entries for 1 rows");
+
+        entries.store(1, Ordering::SeqCst);
+        let found = find_corruption(&client, &status.addr, &table, &pool).await;
+        assert_eq!(found.unwrap(), None);
+
+        status.route("/mvcc/key/test/t/1", 200, ABSENT);
+        let found = find_corruption(&client, &status.addr, &table, &pool).await;
+        assert!(found.is_err());
+    }
+
+    #[tokio::test]
+    async fn find_wrong_handle() {
+        let status = MockStatusServer::start().await.unwrap();
+        let mysql = MockMySqlServer::start(status.ddl_id()).await.unwrap();
+        mysql.on(move |sql| match sql {
+            "SELECT DATABASE()" => Some(Reply::rows(&["db"], vec![vec![Some("test".into())]])),
+            _ if sql.starts_with("SELECT CAST(_tidb_rowid") => Some(Reply::rows(
+                &["h", "c1", "c2"],
+                vec![vec![Some("1".into()), Some("10".into()), Some("20".into())]],
+            )),
+            _ if sql.starts_with("SELECT CAST(COUNT(*)") => {
+                Some(Reply::rows(&["count"], vec![vec![Some("1".into())]]))
+            }
+            _ => None,
+        });
+        let client = reqwest::Client::new();
+        let pool = MySqlPoolOptions::new().connect(&mysql.uri()).await.unwrap();
+        let table =
+            Table::from_create_statement("CREATE TABLE t (c1 INT, c2 INT, UNIQUE KEY i1 (c1))")
+                .unwrap();
+
+        let entry = |value: &str| {
+            format!(
+                r#"{{"key": "", "region_id": 2, "value": {{"info": {{"writes": [{{"start_ts": 2, "commit_ts": 3, "short_value": "{}"}}]}}}}}}"#,
+                value
+            )
+        };
+        status.route("/mvcc/key/test/t/1", 200, ROW);
+        status.route("/mvcc/index/test/t/i1/1", 200, &entry("AAAAAAAAAAE="));
+        let found = find_corruption(&client, &status.addr, &table, &pool).await;
+        assert_eq!(found.unwrap(), None);
+
+        status.route("/mvcc/index/test/t/i1/1", 200, &entry("AAAAAAAAAAI="));
+        let found = find_corruption(&client, &status.addr, &table, &pool).await;
+        assert_eq!(
+            found.unwrap().unwrap(),
+            "index i1 points the row (10, 20) to Int(2) instead of Int(1)"
+        );
+    }
+
+    #[test]
+    fn decode_index_values() {
+        // before 5.0, with the untouched flag
+        assert_eq!(
+            decode_index_value(&[0, 0, 0, 0, 0, 0, 0, 7, b'1']),
+            Ok(Handle::Int(7))
+        );
+        // the int handle in the tail, after restored data
+        let mut value = vec![8, 128, 0, 1];
+        value.extend(7i64.to_be_bytes());
+        assert_eq!(decode_index_value(&value), Ok(Handle::Int(7)));
+        // a common handle after the partition id, and padding
+        let mut value = vec![2, 126, 0, 0, 0, 0, 0, 0, 0, 9, 127, 0, 3, 1, 2, 3, 0, 0];
+        assert_eq!(
+            decode_index_value(&value),
+            Ok(Handle::Common(vec![1, 2, 3]))
+        );
+        value.truncate(14);
+        value[0] = 0;
+        assert!(decode_index_value(&value).is_err());
+    }
+}
@@ -0,0 +1,277 @@
+use crate::table::Datum;
+use crate::workload::{Case, Workload};
+use crate::{Result, Results};
+use lazy_static::lazy_static;
+use slog::info;
+use sqlx::MySql;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+// something that happens in a case, in the order of the trace
+#[derive(Clone, Debug)]
+pub enum Event {
+    // a statement is sent on the connection `conn`, `params` are bound if it's a prepared one
+    Sent {
+        conn: usize,
+        sql: String,
+        params: Option<Vec<Datum>>,
+    },
+    // the statement in flight on the connection `conn` has returned
+    Returned {
+        conn: usize,
+    },
+    // a call to the failpoint API, `value` is `None` to disable
+    Failpoint {
+        status_addr: String,
+        name: String,
+        value: Option<String>,
+    },
+}
+
+// The trace of a case, so that it can be exported as a script. Statements are recorded with their
+// connections, and when they return, so concurrent connections can be replayed in the same order.
+// Nothing is recorded unless `start` is called. In a dry run, failpoint calls are only recorded.
+struct Trace {
+    events: Vec<Event>,
+    dry: bool,
+}
+
+lazy_static! {
+    static ref TRACE: Mutex<Option<Trace>> = Mutex::new(None);
+}
+
+// the number of `Untraced` guards alive
+static UNTRACED: AtomicUsize = AtomicUsize::new(0);
+
+pub fn start(dry: bool) {
+    *TRACE.lock().unwrap() = Some(Trace {
+        events: vec![],
+        dry,
+    });
+}
+
+pub fn finish() -> Vec<Event> {
+    TRACE
+        .lock()
+        .unwrap()
+        .take()
+        .map(|trace| trace.events)
+        .unwrap_or_default()
+}
+
+// Statements sent while it's alive are not recorded, e.g. warming up and checking the data, which
+// are not a part of the case.
+pub struct Untraced(());
+
+pub fn untraced() -> Untraced {
+    UNTRACED.fetch_add(1, Ordering::SeqCst);
+    Untraced(())
+}
+
+impl Drop for Untraced {
+    fn drop(&mut self) {
+        UNTRACED.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+// `event` is only built when recording
+fn record(event: impl FnOnce() -> Event) {
+    if UNTRACED.load(Ordering::SeqCst) > 0 {
+        return;
+    }
+    if let Some(trace) = TRACE.lock().unwrap().as_mut() {
+        trace.events.push(event());
+    }
+}
+
+// record a statement sent by the text protocol, or a prepared one with its params
+pub fn record_statement(conn: usize, sql: &str, params: Option<&[Datum]>) {
+    record(|| Event::Sent {
+        conn,
+        sql: sql.to_owned(),
+        params: params.map(<[Datum]>::to_vec),
+    });
+}
+
+pub fn record_returned(conn: usize) {
+    record(|| Event::Returned { conn });
+}
+
+// Records a call to the failpoint API, `value` is `None` to disable. Returns whether the call must
+// not be sent, i.e. it's a dry run.
+pub fn record_failpoint(status_addr: &str, name: &str, value: Option<&str>) -> bool {
+    let mut trace = TRACE.lock().unwrap();
+    match trace.as_mut() {
+        Some(trace) => {
+            trace.events.push(Event::Failpoint {
+                status_addr: status_addr.to_owned(),
+                name: name.to_owned(),
+                value: value.map(ToOwned::to_owned),
+            });
+            trace.dry
+        }
+        None => false,
+    }
+}
+
+// Statements that can be recorded by `send!`. Prepared statements are recorded when they are
+// built, see `workload::prepared`, because the parameters are not accessible from a `Query`.
+pub trait Traced {
+    fn text(&self) -> Option<&str>;
+}
+
+impl Traced for &str {
+    fn text(&self) -> Option<&str> {
+        Some(self)
+    }
+}
+
+impl<'q, A> Traced for sqlx::query::Query<'q, MySql, A> {
+    fn text(&self) -> Option<&str> {
+        None
+    }
+}
+
+// record a statement on its way to be sent on the connection `conn`
+pub fn traced<T: Traced>(conn: usize, q: T) -> T {
+    if let Some(sql) = q.text() {
+        record_statement(conn, sql, None);
+    }
+    q
+}
+
+// Runs a case and returns a script of it. It's a dry run: the failpoint calls are recorded but
+// not sent, so no corrupted data is written, and the script makes them against the build under
+// test.
+pub async fn repro(case: &Case<'_>, workload: &Arc<dyn Workload>) -> Result<String> {
+    let Case {
+        log,
+        session,
+        term,
+        table,
+        injection,
+        ..
+    } = case;
+    info!(log, "recording"; "table" => &table.name, "injection" => injection.name, "session" => %session, "term" => %term);
+    let mut results = Results::new();
+    start(true);
+    let res = case.run(workload.execute(case, &mut results)).await;
+    let trace = finish();
+    res?;
+    Ok(script(case, workload.name(), None, &trace))
+}
+
+// The bash script of a case run by `workload`, from its trace. Each connection of the case is a
+// mysql session that reads from a fifo. The statements and the failpoint calls are sent in the
+// recorded order, and wherever a statement returned in the trace, the script waits for its
+// session to get past it. Prepared statements are sent by PREPARE and EXECUTE. `note` is added to
+// the header.
+pub fn script(case: &Case, workload: &str, note: Option<&str>, trace: &[Event]) -> String {
+    let mut script = format!(
+        "#!/bin/bash\n\
+         # table: {}, workload: {}, injection: {}, session: {}, term: {}\n",
+        case.table.name, workload, case.injection, case.session, case.term
+    );
+    if let Some(note) = note {
+        script.push_str(&format!("# {}\n", note));
+    }
+    script.push_str(
+        "# errors are part of the case, the output of each session is printed at the end\n\
+         mysql=${MYSQL:-\"mysql -h 127.0.0.1 -P 4000 -u root -D test\"}\n\
+         dir=$(mktemp -d)\n\
+         wait_for() { until grep -qx \"$2\" \"$dir/$1.out\"; do sleep 0.01; done; }\n",
+    );
+
+    // the sessions by the connections, in the order they are first used
+    let mut sessions = HashMap::new();
+    // the marker of the statement in flight on each connection
+    let mut in_flight = HashMap::new();
+    // the names of the prepared statements of each connection, by their SQL
+    let mut prepared: HashMap<(usize, &str), String> = HashMap::new();
+    for (i, event) in trace.iter().enumerate() {
+        match event {
+            Event::Sent { conn, sql, params } => {
+                let session = match sessions.get(conn) {
+                    Some(&session) => session,
+                    None => {
+                        let session = sessions.len();
+                        sessions.insert(*conn, session);
+                        script.push_str(&format!(
+                            "mkfifo \"$dir/c{0}\"\n\
+                             $mysql --force -N -n < \"$dir/c{0}\" > \"$dir/c{0}.out\" 2>&1 &\n\
+                             exec {1}> \"$dir/c{0}\"\n",
+                            session,
+                            session + 10
+                        ));
+                        session
+                    }
+                };
+                script.push_str(&format!("cat >&{} <<'SQL'\n", session + 10));
+                match params {
+                    None => script.push_str(&format!("{};\n", sql)),
+                    Some(params) => {
+                        let count = prepared.len();
+                        let name = prepared.entry((*conn, sql)).or_insert_with(|| {
+                            let name = format!("s{}", count);
+                            script.push_str(&format!(
+                                "PREPARE {} FROM '{}';\n",
+                                name,
+                                sql.replace('\'', "''")
+                            ));
+                            name
+                        });
+                        let vars = (0..params.len())
+                            .map(|p| format!("@p{}", p))
+                            .collect::<Vec<_>>();
+                        if !params.is_empty() {
+                            script.push_str(&format!(
+                                "SET {};\n",
+                                vars.iter()
+                                    .zip(params)
+                                    .map(|(var, param)| format!("{} = {}", var, param.to_string()))
+                                    .collect::<Vec<_>>()
+                                    .join(", ")
+                            ));
+                            script.push_str(&format!(
+                                "EXECUTE {} USING {};\n",
+                                name,
+                                vars.join(", ")
+                            ));
+                        } else {
+                            script.push_str(&format!("EXECUTE {};\n", name));
+                        }
+                    }
+                }
+                let marker = format!("returned {}", i);
+                script.push_str(&format!("SELECT '{}';\nSQL\n", marker));
+                in_flight.insert(*conn, marker);
+            }
+            Event::Returned { conn } => {
+                if let Some(marker) = in_flight.remove(conn) {
+                    script.push_str(&format!("wait_for c{} '{}'\n", sessions[conn], marker));
+                }
+            }
+            Event::Failpoint {
+                status_addr,
+                name,
+                value,
+            } => script.push_str(&match value {
+                Some(value) => format!(
+                    "curl -s -X PUT -d '{}' http://{}/fail/{}\n",
+                    value, status_addr, name
+                ),
+                None => format!("curl -s -X DELETE http://{}/fail/{}\n", status_addr, name),
+            }),
+        }
+    }
+
+    for session in 0..sessions.len() {
+        script.push_str(&format!("exec {}>&-\n", session + 10));
+    }
+    script.push_str(
+        "wait\n\
+         for out in \"$dir\"/*.out; do echo \"== $out\"; cat \"$out\"; done\n",
+    );
+    script
+}
@@ -0,0 +1,208 @@
+use crate::fuzz::Program;
+use crate::repro::{finish, script, start, Event};
+use crate::workload::{fuzz_case, fuzz_seed, Case, Workload};
+use crate::{Effectiveness, Result, Results};
+use slog::info;
+use std::path::Path;
+use std::sync::Arc;
+
+// Minimises a failing case and writes the reproducer to `shrink_dir`.
+// The table is simplified first, then the program if it's the fuzz workload. Each step takes the
+// first candidate that still fails, until none of them does. The reproducer is the trace of the
+// last run that failed.
+pub async fn shrink(case: &Case<'_>, workload: &Arc<dyn Workload>) -> Result<()> {
+    let Case {
+        log,
+        config,
+        session,
+        term,
+        injection,
+        ..
+    } = case;
+    info!(log, "shrinking"; "table" => &case.table.name, "injection" => injection.name, "session" => %session, "term" => %term);
+    let mut table = case.table.clone();
+    let mut program = (workload.name() == "fuzz")
+        .then(|| Program::generate(fuzz_seed(config, &table, injection.name)));
+    let mut trace = match fails(case, workload, program.as_ref()).await? {
+        Some(trace) => trace,
+        None => {
+            info!(log, "the case doesn't fail again, it's not shrunk");
+            return Ok(());
+        }
+    };
+
+    'table: loop {
+        for candidate in table.simplifications() {
+            if !injection.is_applicable(config, &candidate) {
+                continue;
+            }
+            let case = Case {
+                table: &candidate,
+                ..case.clone()
+            };
+            if let Some(t) = fails(&case, workload, program.as_ref()).await? {
+                info!(log, "shrunk table"; "create statement" => candidate.create_statement());
+                trace = t;
+                table = candidate;
+                continue 'table;
+            }
+        }
+        break;
+    }
+
+    if let Some(program) = &mut program {
+        'program: loop {
+            for candidate in program.shorter() {
+                let case = Case {
+                    table: &table,
+                    ..case.clone()
+                };
+                if let Some(t) = fails(&case, workload, Some(&candidate)).await? {
+                    info!(log, "shrunk program"; "steps" => ?candidate.steps);
+                    trace = t;
+                    *program = candidate;
+                    continue 'program;
+                }
+            }
+            break;
+        }
+    }
+
+    let case = Case {
+        table: &table,
+        ..case.clone()
+    };
+    let path = write_reproducer(&case, workload.name(), program.as_ref(), &trace)?;
+    info!(log, "reproducer written"; "path" => path);
+    Ok(())
+}
+
+// runs the case, and returns its trace if it still fails
+async fn fails(
+    case: &Case<'_>,
+    workload: &Arc<dyn Workload>,
+    program: Option<&Program>,
+) -> Result<Option<Vec<Event>>> {
+    let mut results = Results::new();
+    start(false);
+    let res = match program {
+        Some(program) => case.run(fuzz_case(case, program, &mut results)).await,
+        None => case.run(workload.execute(case, &mut results)).await,
+    };
+    let trace = finish();
+    res?;
+    let failed = results.values().any(|r| {
+        matches!(
+            r.effectiveness,
+            Effectiveness::Failure(_) | Effectiveness::Undetected
+        )
+    });
+    Ok(failed.then_some(trace))
+}
+
+fn write_reproducer(
+    case: &Case,
+    workload: &str,
+    program: Option<&Program>,
+    trace: &[Event],
+) -> Result<String> {
+    let Case {
+        config,
+        session,
+        term,
+        table,
+        injection,
+        ..
+    } = case;
+    std::fs::create_dir_all(&config.shrink_dir)?;
+    let path = Path::new(&config.shrink_dir).join(format!(
+        "{}-{}-{}-{}-{}.sh",
+        table.name,
+        workload,
+        injection,
+        session,
+        term.to_string()
+            .replace(|c: char| !c.is_ascii_alphanumeric(), "_")
+    ));
+    let note = program.map(|program| {
+        format!(
+            "fuzz program of seed {}, shortened to {} steps",
+            program.seed,
+            program.steps.len()
+        )
+    });
+    std::fs::write(&path, script(case, workload, note.as_deref(), trace))?;
+    Ok(path.display().to_string())
+}
+
+#[cfg(test)]
+mod test {
+    use super::shrink;
+    use crate::config::parse_args;
+    use crate::failpoint::new_client;
+    use crate::injection::Injection;
+    use crate::mock::{MockMySqlServer, MockStatusServer, Reply};
+    use crate::table::Table;
+    use crate::workload::{find_workload, Case};
+    use slog::{o, Discard, Logger};
+    use sqlx::mysql::MySqlPoolOptions;
+    use std::sync::Arc;
+
+    // the reproducer replays the DML and the failpoint calls of the last failing run
+    #[tokio::test(flavor = "multi_thread")]
+    async fn reproducer_from_trace() {
+        let status = MockStatusServer::start().await.unwrap();
+        let mysql = MockMySqlServer::start(status.ddl_id()).await.unwrap();
+        mysql.on(|sql| {
+            sql.starts_with("admin check table")
+                .then(|| Reply::data_inconsistent("t"))
+        });
+        let uri = mysql.uri();
+        let dir = std::env::temp_dir().join(format!("corrupttest-shrink-{}", std::process::id()));
+        let config = parse_args([
+            "corrupttest",
+            "-a",
+            "strict",
+            "-m",
+            "1",
+            "-w",
+            "t2",
+            "-u",
+            &uri,
+            "-s",
+            &status.addr,
+            "--shrink_dir",
+            dir.to_str().unwrap(),
+        ]);
+        let client = new_client().unwrap();
+        let pool = Arc::new(MySqlPoolOptions::new().connect(&uri).await.unwrap());
+        let table =
+            Table::from_create_statement("CREATE TABLE t (c1 INT, c2 INT, KEY i1 (c1))").unwrap();
+        let case = Case {
+            log: Logger::root(Discard, o!()),
+            config: &config,
+            session: &config.sessions()[0],
+            term: &config.terms[0],
+            table: &table,
+            injection: Injection::find("missingIndex").unwrap(),
+            client: &client,
+            pool,
+        };
+        shrink(&case, &find_workload("t2")).await.unwrap();
+
+        let files = std::fs::read_dir(&dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .collect::<Vec<_>>();
+        assert_eq!(files.len(), 1);
+        let content = std::fs::read_to_string(&files[0]).unwrap();
+        std::fs::remove_dir_all(&dir).ok();
+        let lines = content.lines().collect::<Vec<_>>();
+        let position = |prefix: &str| lines.iter().position(|l| l.starts_with(prefix)).unwrap();
+        assert!(position("CREATE TABLE t ") < position("curl -s -X PUT"));
+        assert!(position("curl -s -X PUT") < position("INSERT INTO t "));
+        assert!(position("UPDATE t ") < position("curl -s -X DELETE"));
+        // the checks after the case are left out
+        assert!(!content.contains("admin check"));
+    }
+}
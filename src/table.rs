@@ -22,6 +22,7 @@ impl Collation {
 enum ColumnType {
     Int,
     String(Option<String>), // collation
+    Char(Option<String>),   // only left by `modify_column`
 }
 
 impl ToString for ColumnType {
@@ -32,10 +33,16 @@ impl ToString for ColumnType {
                 format!("VARCHAR(10) COLLATE {}", c)
             }
             ColumnType::String(None) => "VARCHAR(10)".to_owned(),
+            ColumnType::Char(Some(c)) => format!("CHAR(10) COLLATE {}", c),
+            ColumnType::Char(None) => "CHAR(10)".to_owned(),
         }
     }
 }
 
+// the number of distinct values `Datum::nth` derives from a string, within VARCHAR(10): "hello"
+// leaves 5 digits
+pub const DISTINCT_VALUES: usize = 100_000;
+
 #[derive(Clone, Debug, Hash, PartialEq, Eq)]
 pub enum Datum {
     Int(i64),
@@ -46,7 +53,7 @@ impl Datum {
     fn new(column_type: &ColumnType) -> Self {
         match column_type {
             ColumnType::Int => Datum::Int(10),
-            ColumnType::String(_) => Datum::String("hello".to_owned()),
+            ColumnType::String(_) | ColumnType::Char(_) => Datum::String("hello".to_owned()),
         }
     }
 
@@ -57,6 +64,17 @@ impl Datum {
             Datum::String(x) => Datum::String(format!("{} x", x)),
         }
     }
+
+    // the n-th distinct value derived from this one. Unlike repeated `next`s, strings only grow
+    // by the digits of `n`, so they still fit in the column.
+    #[must_use]
+    pub fn nth(&self, n: usize) -> Self {
+        match self {
+            Datum::Int(x) => Datum::Int(x + n as i64),
+            Datum::String(x) if n == 0 => Datum::String(x.clone()),
+            Datum::String(x) => Datum::String(format!("{}{}", x, n)),
+        }
+    }
 }
 
 impl ToString for Datum {
@@ -117,7 +135,7 @@ impl IndexColumn {
                         name: col.name.clone(),
                         length: None,
                     },
-                    ColumnType::String(_) => {
+                    ColumnType::String(_) | ColumnType::Char(_) => {
                         yield IndexColumn {
                             name: col.name.clone(),
                             length: Some(10),
@@ -164,6 +182,54 @@ struct Index {
 }
 
 impl Index {
+    // the name TiDB knows the index by, primary keys are always named `PRIMARY`
+    fn sql_name(&self) -> &str {
+        if self.unique.is_primary() {
+            "PRIMARY"
+        } else {
+            &self.name
+        }
+    }
+
+    // the definition of the index in CREATE TABLE
+    fn clause(&self) -> String {
+        format!(
+            "{} KEY {} ({}){}",
+            match self.unique {
+                Uniqueness::NonUnique => "",
+                Uniqueness::Unique => "UNIQUE",
+                _ => "PRIMARY",
+            },
+            self.name,
+            self.columns
+                .iter()
+                .map(|c| format!(
+                    "{}{}",
+                    c.name,
+                    c.length
+                        .map(|l| format!("({})", l))
+                        .unwrap_or_else(|| "".to_owned())
+                ))
+                .collect::<Vec<String>>()
+                .join(", "),
+            match self.unique {
+                Uniqueness::ClusterdPrimary => " CLUSTERED",
+                _ => "",
+            }
+        )
+    }
+
+    // a column may be indexed twice by prefixes, it's only listed once
+    fn column_names(&self) -> Vec<String> {
+        let mut names: Vec<String> = vec![];
+        for c in &self.columns {
+            if !names.contains(&c.name) {
+                names.push(c.name.clone());
+            }
+        }
+        names
+    }
+
     fn stream(name: String, col: Vec<Column>) -> impl Stream<Item = Index> {
         stream! {
             let c1_stream = IndexColumn::stream(col.clone());
@@ -196,6 +262,7 @@ pub struct Table {
     indices: Vec<Index>,
 }
 
+#[derive(Clone)]
 pub struct Row {
     pub cols: Vec<Datum>,
 }
@@ -217,6 +284,13 @@ impl Row {
                 .collect::<Vec<Datum>>(),
         }
     }
+
+    #[must_use]
+    pub fn nth(&self, n: usize) -> Self {
+        Row {
+            cols: self.cols.iter().map(|c| c.nth(n)).collect(),
+        }
+    }
 }
 
 impl ToString for Row {
@@ -229,38 +303,33 @@ impl ToString for Row {
     }
 }
 
+// a DML statement with `?` placeholders. It's either sent as text with the params inlined, or
+// as a prepared statement with the params bound.
+#[derive(Clone, Debug)]
+pub struct Dml {
+    pub sql: String,
+    pub params: Vec<Datum>,
+}
+
+impl Dml {
+    pub fn to_text(&self) -> String {
+        let mut parts = self.sql.split('?');
+        let mut text = parts.next().unwrap().to_owned();
+        for (part, param) in parts.zip(&self.params) {
+            text.push_str(&param.to_string());
+            text.push_str(part);
+        }
+        text
+    }
+}
+
 impl Table {
     pub fn create_statement(&self) -> String {
         let col_clauses = self
             .cols
             .iter()
             .map(|c| format!("{} {}", c.name, c.column_type.to_string()));
-        let index_clauses = self.indices.iter().map(|i| {
-            format!(
-                "{} KEY {} ({}){}",
-                match i.unique {
-                    Uniqueness::NonUnique => "",
-                    Uniqueness::Unique => "UNIQUE",
-                    _ => "PRIMARY",
-                },
-                i.name,
-                i.columns
-                    .iter()
-                    .map(|c| format!(
-                        "{}{}",
-                        c.name,
-                        c.length
-                            .map(|l| format!("({})", l))
-                            .unwrap_or_else(|| "".to_owned())
-                    ))
-                    .collect::<Vec<String>>()
-                    .join(", "),
-                match i.unique {
-                    Uniqueness::ClusterdPrimary => " CLUSTERED",
-                    _ => "",
-                }
-            )
-        });
+        let index_clauses = self.indices.iter().map(Index::clause);
         format!(
             "CREATE TABLE {} ({})",
             self.name,
@@ -271,6 +340,107 @@ impl Table {
         )
     }
 
+    // the inverse of `create_statement`, for tables in the shape of generated ones
+    pub fn from_create_statement(sql: &str) -> Option<Table> {
+        let sql = sql.trim().trim_end_matches(';');
+        let prefix = "CREATE TABLE";
+        if !sql.get(..prefix.len())?.eq_ignore_ascii_case(prefix) {
+            return None;
+        }
+        let rest = &sql[prefix.len()..];
+        let open = rest.find('(')?;
+        let body = rest[open + 1..].trim_end().strip_suffix(')')?;
+        let mut cols = vec![];
+        let mut indices = vec![];
+        for clause in split_top_level(body) {
+            let first = clause.split_whitespace().next()?.to_uppercase();
+            if ["KEY", "UNIQUE", "PRIMARY"].contains(&first.as_str()) {
+                indices.push(parse_index(clause)?);
+            } else {
+                cols.push(parse_column(clause)?);
+            }
+        }
+        Some(Table {
+            name: rest[..open].trim().trim_matches('`').to_owned(),
+            cols,
+            indices,
+        })
+    }
+
+    // add an index on all columns in reverse order, which needs backfilling. Returns the statement
+    // and the table it leaves.
+    pub fn add_index(&self) -> (String, Table) {
+        let index = Index {
+            name: "i_ddl".to_owned(),
+            columns: self
+                .cols
+                .iter()
+                .rev()
+                .map(|c| IndexColumn {
+                    name: c.name.clone(),
+                    length: match c.column_type {
+                        ColumnType::Int => None,
+                        ColumnType::String(_) | ColumnType::Char(_) => Some(10),
+                    },
+                })
+                .collect(),
+            unique: Uniqueness::NonUnique,
+        };
+        let statement = format!("ALTER TABLE {} ADD {}", self.name, index.clause().trim());
+        let mut table = self.clone();
+        table.indices.push(index);
+        (statement, table)
+    }
+
+    // change the type of the first column out of the primary key, in a way that the data needs to
+    // be reorganized: ints become strings, and strings become fixed-length. TiDB can't change the
+    // type of a primary key column, so it's `None` if every column is in it. Returns the statement
+    // and the table it leaves.
+    pub fn modify_column(&self) -> Option<(String, Table)> {
+        let primary = self
+            .indices
+            .iter()
+            .filter(|i| i.unique.is_primary())
+            .flat_map(Index::column_names)
+            .collect::<Vec<_>>();
+        let pos = self.cols.iter().position(|c| !primary.contains(&c.name))?;
+        let mut table = self.clone();
+        let col = &mut table.cols[pos];
+        col.column_type = match &col.column_type {
+            ColumnType::Int => ColumnType::String(None),
+            ColumnType::String(c) | ColumnType::Char(c) => ColumnType::Char(c.clone()),
+        };
+        let statement = format!(
+            "ALTER TABLE {} MODIFY COLUMN {} {}",
+            self.name,
+            col.name,
+            col.column_type.to_string()
+        );
+        Some((statement, table))
+    }
+
+    // a non-transactional DML that splits on the first column, either a deletion of all rows or
+    // an update of the second column
+    pub fn batch_statement(&self, limit: usize, update: bool) -> String {
+        let shard = &self.cols[0].name;
+        let dml = if update {
+            let col = &self.cols[1];
+            let value = match col.column_type {
+                ColumnType::Int => format!("{} + 1000", col.name),
+                ColumnType::String(_) | ColumnType::Char(_) => {
+                    format!("CONCAT({}, 'u')", col.name)
+                }
+            };
+            format!("UPDATE {} SET {} = {}", self.name, col.name, value)
+        } else {
+            format!("DELETE FROM {}", self.name)
+        };
+        format!(
+            "BATCH ON {} LIMIT {} {} WHERE {} IS NOT NULL",
+            shard, limit, dml, shard
+        )
+    }
+
     pub fn drop_statement(&self) -> String {
         format!("DROP TABLE IF EXISTS {}", &self.name)
     }
@@ -279,6 +449,286 @@ impl Table {
         Row::new(&self.cols)
     }
 
+    pub fn insert(&self, rows: &[Row]) -> Dml {
+        self.write_rows("INSERT", rows)
+    }
+
+    pub fn replace(&self, rows: &[Row]) -> Dml {
+        self.write_rows("REPLACE", rows)
+    }
+
+    fn write_rows(&self, verb: &str, rows: &[Row]) -> Dml {
+        let placeholders = format!("({})", vec!["?"; self.cols.len()].join(", "));
+        Dml {
+            sql: format!(
+                "{} INTO {} VALUES {}",
+                verb,
+                self.name,
+                vec![placeholders; rows.len()].join(", ")
+            ),
+            params: rows.iter().flat_map(|r| r.cols.iter().cloned()).collect(),
+        }
+    }
+
+    // change the first column of every row to a value that none of the rows had before, i.e.
+    // `Datum::nth(n)` with `n` shifted by `offset` for ints, and the first letter replaced for
+    // strings, which keeps their length
+    pub fn update_all(&self, offset: usize) -> Dml {
+        let col = &self.cols[0];
+        let value = match col.column_type {
+            ColumnType::Int => format!("{} + {}", col.name, offset),
+            ColumnType::String(_) | ColumnType::Char(_) => {
+                format!("CONCAT('u', SUBSTRING({}, 2))", col.name)
+            }
+        };
+        Dml {
+            sql: format!("UPDATE {} SET {} = {}", self.name, col.name, value),
+            params: vec![],
+        }
+    }
+
+    // set the `set.0`-th column to `set.1` where the `filter.0`-th column equals `filter.1`
+    pub fn update(&self, set: (usize, Datum), filter: (usize, Datum)) -> Dml {
+        Dml {
+            sql: format!(
+                "UPDATE {} SET {} = ? WHERE {} = ?",
+                self.name, self.cols[set.0].name, self.cols[filter.0].name
+            ),
+            params: vec![set.1, filter.1],
+        }
+    }
+
+    // delete where the `filter.0`-th column equals `filter.1`
+    pub fn delete(&self, filter: (usize, Datum)) -> Dml {
+        Dml {
+            sql: format!(
+                "DELETE FROM {} WHERE {} = ?",
+                self.name, self.cols[filter.0].name
+            ),
+            params: vec![filter.1],
+        }
+    }
+
+    // whether the table has an index that is stored apart from the rows, i.e. any index but a
+    // clustered primary key
+    pub fn has_secondary_index(&self) -> bool {
+        self.indices
+            .iter()
+            .any(|i| i.unique != Uniqueness::ClusterdPrimary)
+    }
+
+    pub fn index_names(&self) -> Vec<String> {
+        self.indices
+            .iter()
+            .map(|i| i.sql_name().to_owned())
+            .collect()
+    }
+
+    // the columns of the clustered primary key, or `None` if the rows are keyed by `_tidb_rowid`
+    pub fn clustered_columns(&self) -> Option<Vec<String>> {
+        self.indices
+            .iter()
+            .find(|i| i.unique == Uniqueness::ClusterdPrimary)
+            .map(Index::column_names)
+    }
+
+    // Whether the rows are keyed by an int, i.e. `_tidb_rowid` or a clustered primary key of a
+    // single int column. Otherwise the key is the encoded primary key, a common handle.
+    pub fn has_int_handle(&self) -> bool {
+        match self
+            .indices
+            .iter()
+            .find(|i| i.unique == Uniqueness::ClusterdPrimary)
+        {
+            Some(pk) => {
+                let names = pk.column_names();
+                names.len() == 1
+                    && self
+                        .cols
+                        .iter()
+                        .any(|c| c.name == names[0] && c.column_type == ColumnType::Int)
+            }
+            None => true,
+        }
+    }
+
+    // whether the index named `index` is unique, which keeps the handle in its values
+    pub fn is_unique(&self, index: &str) -> bool {
+        self.indices
+            .iter()
+            .any(|i| i.sql_name() == index && i.unique != Uniqueness::NonUnique)
+    }
+
+    // the indices that are stored apart from the rows, as (name, columns)
+    pub fn secondary_indices(&self) -> Vec<(String, Vec<String>)> {
+        self.indices
+            .iter()
+            .filter(|i| i.unique != Uniqueness::ClusterdPrimary)
+            .map(|i| (i.sql_name().to_owned(), i.column_names()))
+            .collect()
+    }
+
+    // a table scan of all rows, led by `_tidb_rowid` if the table is not clustered.
+    // Values are cast to strings, like in `select_statement`.
+    pub fn scan_statement(&self) -> String {
+        let handle = match self.clustered_columns() {
+            Some(_) => None,
+            None => Some("CAST(_tidb_rowid AS CHAR)".to_owned()),
+        };
+        format!(
+            "SELECT {} FROM {} USE INDEX()",
+            handle
+                .into_iter()
+                .chain(
+                    self.cols
+                        .iter()
+                        .map(|c| format!("CAST({} AS CHAR)", c.name))
+                )
+                .collect::<Vec<String>>()
+                .join(", "),
+            self.name
+        )
+    }
+
+    // a query that reads all rows through index `through`, or by a table scan that ignores every
+    // secondary index if it's `None`. Values are cast to strings, like in `select_statement`.
+    pub fn read_all_statement(&self, through: Option<&str>) -> String {
+        let hint = match through {
+            Some(index) => format!(" USE INDEX({})", index),
+            None => {
+                let indices = self
+                    .secondary_indices()
+                    .into_iter()
+                    .map(|(name, _)| name)
+                    .collect::<Vec<String>>();
+                if indices.is_empty() {
+                    "".to_owned()
+                } else {
+                    format!(" IGNORE INDEX({})", indices.join(", "))
+                }
+            }
+        };
+        format!(
+            "SELECT {} FROM {}{}",
+            self.cols
+                .iter()
+                .map(|c| format!("CAST({} AS CHAR)", c.name))
+                .collect::<Vec<String>>()
+                .join(", "),
+            self.name,
+            hint
+        )
+    }
+
+    // a query that reads `rows` by the columns of index `by`, either through index `through`
+    // or by a table scan if it's `None`.
+    // Point get and index lookup are used for a single row, and batch point get for more rows.
+    // Values are cast to strings so that the results of different access paths can be compared.
+    pub fn select_statement(&self, by: &str, through: Option<&str>, rows: &[Row]) -> String {
+        let index = self
+            .indices
+            .iter()
+            .find(|i| i.sql_name() == by)
+            .expect("index not found");
+        let mut positions = vec![];
+        for c in &index.columns {
+            let pos = self.cols.iter().position(|x| x.name == c.name).unwrap();
+            if !positions.contains(&pos) {
+                positions.push(pos);
+            }
+        }
+        let names = positions
+            .iter()
+            .map(|&p| self.cols[p].name.clone())
+            .collect::<Vec<String>>();
+        let values = |row: &Row| {
+            positions
+                .iter()
+                .map(|&p| row.cols[p].to_string())
+                .collect::<Vec<String>>()
+        };
+        let condition = if rows.len() == 1 {
+            names
+                .iter()
+                .zip(values(&rows[0]))
+                .map(|(n, v)| format!("{} = {}", n, v))
+                .collect::<Vec<String>>()
+                .join(" AND ")
+        } else {
+            format!(
+                "({}) IN ({})",
+                names.join(", "),
+                rows.iter()
+                    .map(|r| format!("({})", values(r).join(", ")))
+                    .collect::<Vec<String>>()
+                    .join(", ")
+            )
+        };
+        format!(
+            "SELECT {} FROM {} USE INDEX({}) WHERE {}",
+            self.cols
+                .iter()
+                .map(|c| format!("CAST({} AS CHAR)", c.name))
+                .collect::<Vec<String>>()
+                .join(", "),
+            self.name,
+            through.unwrap_or(""),
+            condition
+        )
+    }
+
+    // tables that are one step simpler than this one: an index, an index column or a collation is
+    // removed, a string column becomes an int, or an index becomes less unique.
+    // They are used to minimise failing cases.
+    pub fn simplifications(&self) -> Vec<Table> {
+        let mut res = vec![];
+        for i in 0..self.indices.len() {
+            let mut t = self.clone();
+            t.indices.remove(i);
+            res.push(t);
+        }
+        for (i, index) in self.indices.iter().enumerate() {
+            if index.columns.len() > 1 {
+                for j in 0..index.columns.len() {
+                    let mut t = self.clone();
+                    t.indices[i].columns.remove(j);
+                    res.push(t);
+                }
+            }
+            let unique = match index.unique {
+                Uniqueness::NonUnique => None,
+                Uniqueness::Unique => Some(Uniqueness::NonUnique),
+                Uniqueness::ClusterdPrimary => Some(Uniqueness::NonClusteredPrimary),
+                Uniqueness::NonClusteredPrimary => Some(Uniqueness::Unique),
+            };
+            if let Some(unique) = unique {
+                let mut t = self.clone();
+                t.indices[i].unique = unique;
+                res.push(t);
+            }
+        }
+        for (i, col) in self.cols.iter().enumerate() {
+            if let ColumnType::String(collation) = &col.column_type {
+                if collation.is_some() {
+                    let mut t = self.clone();
+                    t.cols[i].column_type = ColumnType::String(None);
+                    res.push(t);
+                }
+                let mut t = self.clone();
+                t.cols[i].column_type = ColumnType::Int;
+                for index in &mut t.indices {
+                    for c in &mut index.columns {
+                        if c.name == col.name {
+                            c.length = None;
+                        }
+                    }
+                }
+                res.push(t);
+            }
+        }
+        res
+    }
+
     fn constraint_satisfied(&self) -> bool {
         let mut satisfied = true;
 
@@ -324,6 +774,25 @@ impl Table {
         true
     }
 
+    // find a generated table by its name or its index in `stream`, or parse a CREATE TABLE
+    // statement
+    pub async fn find(id: &str) -> Option<Table> {
+        if id.trim_start().to_uppercase().starts_with("CREATE") {
+            return Table::from_create_statement(id);
+        }
+        let index = id.parse::<usize>().ok();
+        let tables = Table::stream();
+        pin_mut!(tables);
+        let mut i = 0;
+        while let Some(table) = tables.next().await {
+            if table.name == id || index == Some(i) {
+                return Some(table);
+            }
+            i += 1;
+        }
+        None
+    }
+
     pub fn stream() -> impl Stream<Item = Table> {
         stream! {
             let mut table_count = 0;
@@ -360,6 +829,79 @@ impl Table {
     }
 }
 
+// split by commas that are not in parentheses
+fn split_top_level(s: &str) -> Vec<&str> {
+    let mut parts = vec![];
+    let (mut depth, mut start) = (0, 0);
+    for (i, c) in s.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            ',' if depth == 0 => {
+                parts.push(s[start..i].trim());
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    parts.push(s[start..].trim());
+    parts
+}
+
+fn parse_column(clause: &str) -> Option<Column> {
+    let tokens = clause.split_whitespace().collect::<Vec<_>>();
+    let is_varchar = |t: &str| t.to_uppercase().starts_with("VARCHAR");
+    let is_char = |t: &str| t.to_uppercase().starts_with("CHAR");
+    let column_type = match tokens.get(1..)? {
+        [t] if t.eq_ignore_ascii_case("INT") => ColumnType::Int,
+        [t] if is_varchar(t) => ColumnType::String(None),
+        [t] if is_char(t) => ColumnType::Char(None),
+        [t, collate, c] if is_varchar(t) && collate.eq_ignore_ascii_case("COLLATE") => {
+            ColumnType::String(Some(c.to_string()))
+        }
+        [t, collate, c] if is_char(t) && collate.eq_ignore_ascii_case("COLLATE") => {
+            ColumnType::Char(Some(c.to_string()))
+        }
+        _ => return None,
+    };
+    Some(Column {
+        name: tokens[0].trim_matches('`').to_owned(),
+        column_type,
+    })
+}
+
+fn parse_index(clause: &str) -> Option<Index> {
+    let upper = clause.to_uppercase();
+    let key = upper.find("KEY")?;
+    let open = clause.find('(')?;
+    let close = clause.rfind(')')?;
+    let unique = match (upper[..key].trim(), upper[close + 1..].trim()) {
+        ("", "") => Uniqueness::NonUnique,
+        ("UNIQUE", "") => Uniqueness::Unique,
+        ("PRIMARY", "CLUSTERED") => Uniqueness::ClusterdPrimary,
+        ("PRIMARY", "" | "NONCLUSTERED") => Uniqueness::NonClusteredPrimary,
+        _ => return None,
+    };
+    let columns = clause[open + 1..close]
+        .split(',')
+        .map(|c| match c.trim().split_once('(') {
+            Some((name, length)) => Some(IndexColumn {
+                name: name.trim().to_owned(),
+                length: Some(length.trim_end_matches(')').parse().ok()?),
+            }),
+            None => Some(IndexColumn {
+                name: c.trim().to_owned(),
+                length: None,
+            }),
+        })
+        .collect::<Option<Vec<_>>>()?;
+    Some(Index {
+        name: clause[key + "KEY".len()..open].trim().to_owned(),
+        columns,
+        unique,
+    })
+}
+
 #[cfg(test)]
 mod test {
     use crate::table::Table;
@@ -382,4 +924,41 @@ mod test {
         }
         info!(log, "{}", cnt);
     }
+
+    #[tokio::test]
+    async fn parse_create_statement() {
+        let table_stream = Table::stream();
+        pin_mut!(table_stream);
+        while let Some(t) = table_stream.next().await {
+            assert_eq!(Table::from_create_statement(&t.create_statement()), Some(t));
+        }
+    }
+
+    #[test]
+    fn modify_column_out_of_primary_key() {
+        let table = Table::from_create_statement(
+            "CREATE TABLE t (c1 INT, c2 INT, PRIMARY KEY (c1) CLUSTERED)",
+        )
+        .unwrap();
+        let (statement, modified) = table.modify_column().unwrap();
+        assert_eq!(statement, "ALTER TABLE t MODIFY COLUMN c2 VARCHAR(10)");
+        // the column is a string now, so changing it again makes it fixed-length
+        assert_eq!(
+            modified.modify_column().unwrap().0,
+            "ALTER TABLE t MODIFY COLUMN c2 CHAR(10)"
+        );
+        let table = Table::from_create_statement(
+            "CREATE TABLE t (c1 INT, c2 INT, PRIMARY KEY (c2, c1) CLUSTERED)",
+        )
+        .unwrap();
+        assert!(table.modify_column().is_none());
+        // a nonclustered primary key can't be changed either
+        let table = Table::from_create_statement(
+            "CREATE TABLE t (c1 VARCHAR(10), c2 INT, PRIMARY KEY (c1), KEY i1 (c2, c1(10)))",
+        )
+        .unwrap();
+        let (statement, modified) = table.modify_column().unwrap();
+        assert_eq!(statement, "ALTER TABLE t MODIFY COLUMN c2 VARCHAR(10)");
+        assert_eq!(modified.secondary_indices(), table.secondary_indices());
+    }
 }
@@ -1,62 +1,271 @@
-use crate::config::Config;
-use crate::Result;
+use crate::config::{CommitProtocol, Config, Session, StatementProtocol, Term};
 use crate::CREATE_TABLE_DURAION_MS;
+use crate::{error::MyError, Result, Results};
 use crate::{
-    failpoint::{disable_failpoint, enable_failpoint},
-    table::Table,
-    Effectiveness, AVAILABLE_INJECTIONS,
+    failpoint::{check_instance, FailpointGuard},
+    fuzz::Program,
+    injection::Injection,
+    mvcc::find_corruption,
+    repro::{record_returned, record_statement, traced, untraced},
+    table::{Datum, Dml, Row, Table},
+    CaseResult, Effectiveness,
 };
 use async_trait::async_trait;
+use futures::future::join_all;
 use lazy_static::lazy_static;
-use slog::{info, Logger};
+use regex::Regex;
+use slog::{info, warn, Logger};
+use sqlx::mysql::MySqlArguments;
+use sqlx::pool::PoolConnection;
+use sqlx::query::Query;
 use sqlx::MySqlConnection;
-use sqlx::{query, Executor, MySql, Pool};
+use sqlx::{query, Executor, MySql, Pool, Row as _};
+use std::collections::hash_map::DefaultHasher;
 use std::collections::HashMap;
-use std::sync::atomic::Ordering;
+use std::future::Future;
+use std::hash::{Hash, Hasher};
+use std::ops::{Deref, DerefMut};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use std::sync::Arc;
 use tokio::time::Instant;
 
 lazy_static! {
     pub static ref WORKLOADS: HashMap<&'static str, Arc<dyn Workload + Sync + Send>> = {
-        let mut m: HashMap<&'static str, Arc<dyn Workload + Sync + Send>> = HashMap::new();
-        m.insert("single", Arc::new(SingleInsertion));
-        m.insert("double", Arc::new(DoubleInsertion));
-        m.insert("t2", Arc::new(T2));
-        m.insert("t3", Arc::new(T3));
-        m.insert("t4", Arc::new(T4));
-        m
+        let workloads: Vec<Arc<dyn Workload + Sync + Send>> = vec![
+            Arc::new(SingleInsertion),
+            Arc::new(DoubleInsertion),
+            Arc::new(T2),
+            Arc::new(T3),
+            Arc::new(T4),
+            Arc::new(ReadPath),
+            Arc::new(Conflict),
+            Arc::new(LargeTxn),
+            Arc::new(OnlineDdl(DdlKind::AddIndex)),
+            Arc::new(OnlineDdl(DdlKind::ModifyColumn)),
+            Arc::new(NonTransactional { update: false }),
+            Arc::new(NonTransactional { update: true }),
+            Arc::new(Fuzz),
+            Arc::new(Savepoint {
+                inject_after: false,
+            }),
+            Arc::new(Savepoint { inject_after: true }),
+        ];
+        workloads.into_iter().map(|w| (w.name(), w)).collect()
     };
 }
 
+// send a statement on a `Conn`, it's traced until it returns
 macro_rules! send {
-    ($conn:ident, $q: expr) => {
-        $conn.execute($q).await
-    };
+    ($conn:ident, $q: expr) => {{
+        let id = $conn.id;
+        let res = $conn.execute(traced(id, $q)).await;
+        record_returned(id);
+        res
+    }};
     ($log:ident, $conn:ident, $q: expr) => {
         {
             info!($log, "executing"; "query" => $q.to_string());
-            $conn.execute($q).await
+            send!($conn, $q)
         }
     };
 }
 
-// a helper function that collects the results of an execution of a workload
-async fn collect_result(
+// send a DML statement with the protocol of the session
+macro_rules! send_dml {
+    ($log:ident, $conn:ident, $session:ident, $dml: expr) => {{
+        let dml: &Dml = &$dml;
+        match $session.protocol {
+            StatementProtocol::Text => send!($log, $conn, dml.to_text().as_str()),
+            StatementProtocol::Binary => {
+                info!($log, "executing"; "statement" => &dml.sql, "params" => ?dml.params);
+                let id = $conn.id;
+                let res = $conn.execute(prepared(id, dml)).await;
+                record_returned(id);
+                res
+            }
+        }
+    }};
+}
+
+// A connection of a case. Its statements are traced by its id, so that the script of the case
+// replays them in a session of their own.
+struct Conn {
+    id: usize,
+    inner: PoolConnection<MySql>,
+}
+
+static NEXT_CONN_ID: AtomicUsize = AtomicUsize::new(0);
+
+async fn acquire(pool: &Pool<MySql>) -> std::result::Result<Conn, sqlx::Error> {
+    Ok(Conn {
+        id: NEXT_CONN_ID.fetch_add(1, Ordering::SeqCst),
+        inner: pool.acquire().await?,
+    })
+}
+
+impl Deref for Conn {
+    type Target = MySqlConnection;
+
+    fn deref(&self) -> &MySqlConnection {
+        &self.inner
+    }
+}
+
+impl DerefMut for Conn {
+    fn deref_mut(&mut self) -> &mut MySqlConnection {
+        &mut self.inner
+    }
+}
+
+// sqlx caches prepared statements in each connection, so a statement is only prepared once.
+fn prepared(conn: usize, dml: &Dml) -> Query<'_, MySql, MySqlArguments> {
+    record_statement(conn, &dml.sql, Some(&dml.params));
+    dml.params
+        .iter()
+        .fold(query(&dml.sql), |q, param| match param {
+            Datum::Int(x) => q.bind(*x),
+            Datum::String(x) => q.bind(x.as_str()),
+        })
+}
+
+// In the binary protocol, execute the statements once in a txn that is rolled back, so that the
+// executions in the workload are the second ones and hit the plan cache.
+// It must be called when the failpoint is disabled.
+async fn warm_up(log: &Logger, conn: &mut Conn, session: &Session, dmls: &[&Dml]) -> Result<()> {
+    if session.protocol == StatementProtocol::Text {
+        return Ok(());
+    }
+    let _untraced = untraced();
+    send!(log, conn, "begin optimistic")?;
+    for &dml in dmls {
+        // the statements may depend on each other, their errors don't matter here
+        send_dml!(log, conn, session, dml).ok();
+    }
+    send!(log, conn, "rollback")?;
+    Ok(())
+}
+
+pub fn is_inconsistency_error(e: &sqlx::Error) -> bool {
+    let msg = e.to_string().to_lowercase();
+    msg.contains("inconsist") || msg.contains("assertion")
+}
+
+// errors that are expected when concurrent txns conflict
+fn is_conflict_error(e: &sqlx::Error) -> bool {
+    let msg = e.to_string().to_lowercase();
+    msg.contains("write conflict") || msg.contains("deadlock") || msg.contains("lock wait timeout")
+}
+
+// statement-level errors that a random sequence of DML runs into
+fn is_duplicate_error(e: &sqlx::Error) -> bool {
+    e.to_string().to_lowercase().contains("duplicate entry")
+}
+
+// Takes the outcome of a case from its error. Statements may fail as a part of the case, but any
+// other error, e.g. failing to enable the failpoint, is returned without a result.
+fn outcome(res: Result<()>) -> Result<std::result::Result<(), sqlx::Error>> {
+    match res {
+        Ok(()) => Ok(Ok(())),
+        Err(MyError::SqlxError { sqlx, .. }) => Ok(Err(sqlx)),
+        Err(e) => Err(e),
+    }
+}
+
+// the outcome of reading written data back, see `check_reads`
+enum ReadResult {
+    Consistent,
+    Mismatch(String), // an access path of the index returns different rows from a table scan
+    Error(sqlx::Error),
+}
+
+// An injection into a workload on a table, in a session and with a failpoint term. A workload
+// runs one case at a time, and its result is keyed by the case.
+#[derive(Clone)]
+pub struct Case<'a> {
+    pub log: Logger,
+    pub config: &'a Config,
+    pub session: &'a Session,
+    pub term: &'a Term,
+    pub table: &'a Table,
+    pub injection: &'a Injection,
+    pub client: &'a reqwest::Client,
+    pub pool: Arc<Pool<MySql>>,
+}
+
+impl Case<'_> {
+    // the failpoint of the injection, on all instances it targets
+    pub fn failpoint(&self) -> FailpointGuard {
+        FailpointGuard::new(
+            &self.log,
+            self.injection.status_addrs(self.config),
+            self.injection.failpoint,
+        )
+    }
+
+    // Runs the case by `workload`, and disables its failpoint afterwards, in case the workload
+    // returned early and left it enabled.
+    pub async fn run(&self, workload: impl Future<Output = Result<()>>) -> Result<()> {
+        let res = workload.await;
+        let disabled = self.failpoint().disable(self.client).await;
+        res.and(disabled)
+    }
+
+    // the key of the result of the case run by `workload`
+    pub fn key(&self, workload: &str) -> (Table, String, String, Session, Term) {
+        (
+            self.table.clone(),
+            workload.to_owned(),
+            self.injection.name.to_owned(),
+            self.session.clone(),
+            self.term.clone(),
+        )
+    }
+}
+
+// a helper function that collects the result of a case run by `workload`, and returns it to be
+// completed by the workload.
+// `reads` is only given by workloads that read the data back after writing it.
+async fn collect_result<'r>(
+    case: &Case<'_>,
+    workload: &str,
     res: std::result::Result<(), sqlx::Error>,
-    results: &mut HashMap<(Table, String, String), Effectiveness>,
+    reads: Option<ReadResult>,
+    results: &'r mut Results,
+) -> &'r mut CaseResult {
+    collect_result_as(case, case.table, workload, res, reads, results).await
+}
+
+// like `collect_result`, but the data is checked as `table`, which a DDL of the case has left
+async fn collect_result_as<'r>(
+    case: &Case<'_>,
     table: &Table,
-    injection: &str,
-    pool: Arc<Pool<MySql>>,
-) {
+    workload: &str,
+    res: std::result::Result<(), sqlx::Error>,
+    reads: Option<ReadResult>,
+    results: &'r mut Results,
+) -> &'r mut CaseResult {
+    let Case { log, pool, .. } = case;
+    let _untraced = untraced();
     let e = match res {
-        Ok(_) => match send!(pool, format!("admin check table {}", table.name).as_str()) {
-            Ok(_) => Effectiveness::Consistent,
-            Err(_) => Effectiveness::Failure,
+        Ok(_) => match reads {
+            Some(ReadResult::Error(x)) if is_inconsistency_error(&x) => Effectiveness::ReadSuccess,
+            Some(ReadResult::Error(_)) => Effectiveness::OtherError,
+            // the read path returned corrupted data without complaining
+            Some(ReadResult::Mismatch(index)) => Effectiveness::Failure(Some(index)),
+            Some(ReadResult::Consistent) | None => match check_indices(log, table, pool).await {
+                Ok(Some(index)) => Effectiveness::Failure(Some(index)),
+                // the indices can't be checked, so the case tells nothing
+                Err(_) => Effectiveness::OtherError,
+                Ok(None) => match pool
+                    .execute(format!("admin check table {}", table.name).as_str())
+                    .await
+                {
+                    Ok(_) => check_storage(case, table).await,
+                    Err(_) => Effectiveness::Failure(None),
+                },
+            },
         },
-        Err(x)
-            if x.to_string().to_lowercase().contains("inconsist")
-                || x.to_string().to_lowercase().contains("assertion") =>
-        {
+        Err(x) if is_inconsistency_error(&x) => {
             // note: if we run `admin check table` here and get no error, it doesn't mean it's a misreport.
             // It's possible that the txn containing corrupted data is aborted because inconsistency is detected.
             // Then admin check table will not report inconsistency because corrupted data is not written to TiKV.
@@ -64,44 +273,189 @@ async fn collect_result(
         }
         Err(_) => Effectiveness::OtherError,
     };
-    results.insert(
-        (table.clone(), "workload_a".into(), injection.to_string()),
-        e,
-    );
+    let result = CaseResult {
+        effectiveness: e,
+        jobs: None,
+        txn_size: None,
+    };
+    results
+        .entry(case.key(workload))
+        .insert_entry(result)
+        .into_mut()
+}
+
+// Checks each secondary index after a case, by `admin check index` and by comparing the rows read
+// through it with a table scan. Returns the first inconsistent index, or the error that isn't
+// caused by inconsistency.
+async fn check_indices(
+    log: &Logger,
+    table: &Table,
+    pool: &Pool<MySql>,
+) -> std::result::Result<Option<String>, sqlx::Error> {
+    for (index, _) in table.secondary_indices() {
+        let res = async {
+            let mut conn = acquire(pool).await?;
+            send!(
+                log,
+                conn,
+                format!("admin check index {} {}", table.name, index).as_str()
+            )?;
+            let through =
+                fetch_rows(log, &mut conn, &table.read_all_statement(Some(&index))).await?;
+            let scan = fetch_rows(log, &mut conn, &table.read_all_statement(None)).await?;
+            Ok::<_, sqlx::Error>((through, scan))
+        }
+        .await;
+        match res {
+            Ok((through, scan)) if through == scan => {}
+            Ok((through, scan)) => {
+                let missing = scan
+                    .iter()
+                    .filter(|r| !through.contains(r))
+                    .collect::<Vec<_>>();
+                let extra = through
+                    .iter()
+                    .filter(|r| !scan.contains(r))
+                    .collect::<Vec<_>>();
+                info!(log, "index read mismatch"; "index" => &index, "missing" => ?missing, "extra" => ?extra);
+                return Ok(Some(index));
+            }
+            Err(e) if is_inconsistency_error(&e) => {
+                info!(log, "index check failed"; "index" => &index, "error" => ?e);
+                return Ok(Some(index));
+            }
+            Err(e) => {
+                warn!(log, "failed to check the index"; "index" => &index, "error" => ?e);
+                return Err(e);
+            }
+        }
+    }
+    Ok(None)
+}
+
+// `admin check table` has passed, tell an injection without effect from corruption that it misses
+async fn check_storage(case: &Case<'_>, table: &Table) -> Effectiveness {
+    let Case {
+        log,
+        config,
+        client,
+        pool,
+        ..
+    } = case;
+    if !config.mvcc {
+        return Effectiveness::Consistent;
+    }
+    // every TiDB instance reads the same storage, the MVCC API of one of them is enough
+    match find_corruption(client, &config.status_addrs[0], table, pool).await {
+        Ok(None) => Effectiveness::Consistent,
+        Ok(Some(corruption)) => {
+            warn!(log, "corruption is not found by admin check table"; "table" => &table.name, "corruption" => corruption);
+            Effectiveness::Undetected
+        }
+        // the keys are unchecked, the case can't be told consistent
+        Err(e) => {
+            warn!(log, "failed to check the keys with the MVCC API"; "table" => &table.name, "error" => ?e);
+            Effectiveness::OtherError
+        }
+    }
+}
+
+// a helper function that runs a query and returns its rows as strings, sorted.
+async fn fetch_rows(
+    log: &Logger,
+    conn: &mut Conn,
+    q: &str,
+) -> std::result::Result<Vec<Vec<Option<String>>>, sqlx::Error> {
+    info!(log, "executing"; "query" => q);
+    let id = conn.id;
+    let rows = conn.fetch_all(traced(id, q)).await;
+    record_returned(id);
+    let mut rows = rows?
+        .iter()
+        .map(|r| (0..r.len()).map(|i| r.try_get(i)).collect())
+        .collect::<std::result::Result<Vec<Vec<Option<String>>>, _>>()?;
+    rows.sort();
+    Ok(rows)
+}
+
+// read `rows` back through every index, by point get / index lookup, batch point get and
+// locking read, and compare them with the results of table scans.
+async fn check_reads(log: &Logger, conn: &mut Conn, table: &Table, rows: &[Row]) -> ReadResult {
+    let res = async {
+        for index in table.index_names() {
+            for rows in [&rows[..1], rows] {
+                let scan =
+                    fetch_rows(log, conn, &table.select_statement(&index, None, rows)).await?;
+                let read = table.select_statement(&index, Some(&index), rows);
+                if fetch_rows(log, conn, &read).await? != scan {
+                    return Ok(Some((index, read)));
+                }
+                let locking_read = format!("{} FOR UPDATE", read);
+                send!(log, conn, "begin pessimistic")?;
+                let locked = fetch_rows(log, conn, &locking_read).await?;
+                send!(log, conn, "commit")?;
+                if locked != scan {
+                    return Ok(Some((index, locking_read)));
+                }
+            }
+        }
+        Ok(None)
+    }
+    .await;
+    match res {
+        Ok(None) => ReadResult::Consistent,
+        Ok(Some((index, q))) => {
+            info!(log, "read mismatch"; "query" => &q);
+            ReadResult::Mismatch(index)
+        }
+        Err(e) => {
+            send!(log, conn, "rollback").ok();
+            ReadResult::Error(e)
+        }
+    }
 }
 
 // a helper function
 // system variables should be set in the same connection that will run the workload.
-async fn enable_featuers(conn: &mut MySqlConnection, config: &Config) -> Result<()> {
+async fn setup_session(conn: &mut Conn, config: &Config, session: &Session) -> Result<()> {
     send!(
         conn,
-        query(
-            format!(
-                "set @@tidb_enable_mutation_checker = {}",
-                config.mutation_checker
-            )
-            .as_str(),
+        format!(
+            "set @@tidb_enable_mutation_checker = {}",
+            config.mutation_checker
         )
+        .as_str()
     )?;
 
     send!(
         conn,
         format!("set @@tidb_txn_assertion_level = {}", config.assertion).as_str()
     )?;
-    Ok(())
+
+    let (async_commit, one_pc) = match session.commit_protocol {
+        CommitProtocol::TwoPhase => ("off", "off"),
+        CommitProtocol::AsyncCommit => ("on", "off"),
+        // 1PC falls back to async commit when the mutations don't fit in a single region
+        CommitProtocol::OnePhase => ("on", "on"),
+    };
+    send!(
+        conn,
+        format!("set @@tidb_enable_async_commit = {}", async_commit).as_str()
+    )?;
+    send!(conn, format!("set @@tidb_enable_1pc = {}", one_pc).as_str())?;
+    for (name, value) in &session.vars {
+        send!(conn, format!("set @@{} = {}", name, value).as_str())?;
+    }
+    check_instance(conn).await
 }
 
 #[async_trait]
 pub trait Workload {
-    async fn execute(
-        &self,
-        log: Logger,
-        config: &Config,
-        table: Table,
-        client: &reqwest::Client,
-        pool: Arc<Pool<MySql>>,
-        results: &mut HashMap<(Table, String, String), Effectiveness>,
-    ) -> Result<()>;
+    // the name to choose the workload by, and to key its results
+    fn name(&self) -> &'static str;
+
+    // run a case, and collect its result into `results`
+    async fn execute(&self, case: &Case, results: &mut Results) -> Result<()>;
 }
 
 pub fn find_workload(name: &str) -> Arc<dyn Workload> {
@@ -112,48 +466,39 @@ pub fn find_workload(name: &str) -> Arc<dyn Workload> {
 struct SingleInsertion;
 #[async_trait]
 impl Workload for SingleInsertion {
-    async fn execute(
-        &self,
-        log: Logger,
-        config: &Config,
-        table: Table,
-        client: &reqwest::Client,
-        pool: Arc<Pool<MySql>>,
-        results: &mut HashMap<(Table, String, String), Effectiveness>,
-    ) -> Result<()> {
-        let mut conn = pool.acquire().await?;
-        enable_featuers(&mut conn, config).await?;
+    fn name(&self) -> &'static str {
+        "single"
+    }
+
+    async fn execute(&self, case: &Case, results: &mut Results) -> Result<()> {
+        let Case {
+            log,
+            config,
+            session,
+            term,
+            table,
+            injection,
+            client,
+            pool,
+        } = case;
+        let mut conn = acquire(pool).await?;
+        setup_session(&mut conn, config, session).await?;
         let drop_statement = table.drop_statement();
         let create_statement = table.create_statement();
 
-        for injection in AVAILABLE_INJECTIONS {
-            send!(log, conn, drop_statement.as_str()).expect("don't let drop statement fail");
-            send!(log, conn, create_statement.as_str()).expect("don't let create statement fail");
-            enable_failpoint(
-                &log,
-                client,
-                config.status_addr.clone(),
-                "github.com/pingcap/tidb/table/tables/corruptMutations",
-                format!("return(\"{}\")", injection),
-            )
-            .await?;
-            let insertion = format!(
-                "insert into {} values ({})",
-                table.name,
-                table.new_row().to_string()
-            );
-            let res = send!(log, conn, insertion.as_str()).map(|_| ());
-            info!(log, "workload finished"; "result" => ?res);
-
-            collect_result(res, results, &table, injection, pool.clone()).await;
-            disable_failpoint(
-                &log,
-                client,
-                config.status_addr.clone(),
-                "github.com/pingcap/tidb/table/tables/corruptMutations",
-            )
+        let failpoint = case.failpoint();
+        send!(log, conn, drop_statement.as_str()).expect("don't let drop statement fail");
+        send!(log, conn, create_statement.as_str()).expect("don't let create statement fail");
+        let insertion = table.insert(&[table.new_row()]);
+        warm_up(log, &mut conn, session, &[&insertion]).await?;
+        failpoint
+            .enable(client, injection.value(term, "return"))
             .await?;
-        }
+        let res = send_dml!(log, conn, session, insertion).map(|_| ());
+        info!(log, "workload finished"; "result" => ?res);
+
+        collect_result(case, self.name(), res, None, results).await;
+        failpoint.disable(client).await?;
 
         Ok(())
     }
@@ -163,64 +508,56 @@ impl Workload for SingleInsertion {
 struct DoubleInsertion;
 #[async_trait]
 impl Workload for DoubleInsertion {
-    async fn execute(
-        &self,
-        log: Logger,
-        config: &Config,
-        table: Table,
-        client: &reqwest::Client,
-        pool: Arc<Pool<MySql>>,
-        results: &mut HashMap<(Table, String, String), Effectiveness>,
-    ) -> Result<()> {
-        let mut conn = pool.acquire().await?;
-        enable_featuers(&mut conn, config).await?;
+    fn name(&self) -> &'static str {
+        "double"
+    }
+
+    async fn execute(&self, case: &Case, results: &mut Results) -> Result<()> {
+        let Case {
+            log,
+            config,
+            session,
+            term,
+            table,
+            injection,
+            client,
+            pool,
+        } = case;
+        let mut conn = acquire(pool).await?;
+        setup_session(&mut conn, config, session).await?;
         let drop_statement = table.drop_statement();
         let create_statement = table.create_statement();
 
-        for injection in AVAILABLE_INJECTIONS {
-            send!(log, conn, drop_statement.as_str()).expect("don't let drop statement fail");
-            send!(log, conn, create_statement.as_str()).expect("don't let create statement fail");
-            info!(log, "{} ready to go!", injection);
+        let failpoint = case.failpoint();
+        send!(log, conn, drop_statement.as_str()).expect("don't let drop statement fail");
+        send!(log, conn, create_statement.as_str()).expect("don't let create statement fail");
+        info!(log, "{} ready to go!", injection);
 
-            // NOTE: "1*" here, otherwise an index mutation is missing for each row insertion, thus cannot be detected.
-            enable_failpoint(
-                &log,
-                client,
-                config.status_addr.clone(),
-                "github.com/pingcap/tidb/table/tables/corruptMutations",
-                format!("1*return(\"{}\")", injection),
-            )
-            .await?;
-            send!(log, conn, "BEGIN OPTIMISTIC")?;
-            let row = table.new_row();
-            let insertion_1 = format!("INSERT INTO {} VALUES ({})", table.name, row.to_string());
-            let insertion_2 = format!(
-                "INSERT INTO {} VALUES ({})",
-                table.name,
-                row.next().to_string()
-            );
-
-            let res = async {
-                send!(log, conn, insertion_1.as_str())?;
-                send!(log, conn, insertion_2.as_str())?;
-                Ok(())
-            }
-            .await;
+        let row = table.new_row();
+        let insertion_1 = table.insert(std::slice::from_ref(&row));
+        let insertion_2 = table.insert(&[row.next()]);
+        warm_up(log, &mut conn, session, &[&insertion_1, &insertion_2]).await?;
 
-            if res.is_err() {
-                send!(log, conn, "ROLLBACK")?;
-            }
-            info!(log, "workload finished"; "result" => ?res);
-
-            collect_result(res, results, &table, injection, pool.clone()).await;
-            disable_failpoint(
-                &log,
-                client,
-                config.status_addr.clone(),
-                "github.com/pingcap/tidb/table/tables/corruptMutations",
-            )
+        // NOTE: "1*" here, otherwise an index mutation is missing for each row insertion, thus cannot be detected.
+        failpoint
+            .enable(client, injection.value(term, "1*return"))
             .await?;
+        send!(log, conn, "BEGIN OPTIMISTIC")?;
+
+        let res = async {
+            send_dml!(log, conn, session, insertion_1)?;
+            send_dml!(log, conn, session, insertion_2)?;
+            Ok(())
         }
+        .await;
+
+        if res.is_err() {
+            send!(log, conn, "ROLLBACK")?;
+        }
+        info!(log, "workload finished"; "result" => ?res);
+
+        collect_result(case, self.name(), res, None, results).await;
+        failpoint.disable(client).await?;
 
         Ok(())
     }
@@ -230,73 +567,62 @@ impl Workload for DoubleInsertion {
 struct T2;
 #[async_trait]
 impl Workload for T2 {
-    async fn execute(
-        &self,
-        log: Logger,
-        config: &Config,
-        table: Table,
-        client: &reqwest::Client,
-        pool: Arc<Pool<MySql>>,
-        results: &mut HashMap<(Table, String, String), Effectiveness>,
-    ) -> Result<()> {
-        let mut conn = pool.acquire().await?;
-        enable_featuers(&mut conn, config).await?;
+    fn name(&self) -> &'static str {
+        "t2"
+    }
+
+    async fn execute(&self, case: &Case, results: &mut Results) -> Result<()> {
+        let Case {
+            log,
+            config,
+            session,
+            term,
+            table,
+            injection,
+            client,
+            pool,
+        } = case;
+        let mut conn = acquire(pool).await?;
+        setup_session(&mut conn, config, session).await?;
         let drop_statement = table.drop_statement();
         let create_statement = table.create_statement();
         let start = Instant::now();
         let duration = start.elapsed();
         CREATE_TABLE_DURAION_MS.fetch_add(duration.as_millis() as u64, Ordering::SeqCst);
 
-        for injection in AVAILABLE_INJECTIONS {
-            send!(log, conn, drop_statement.as_str()).expect("don't let drop statement fail");
-            send!(log, conn, create_statement.as_str()).expect("don't let create statement fail");
-            info!(log, "{} ready to go!", injection);
+        let failpoint = case.failpoint();
+        send!(log, conn, drop_statement.as_str()).expect("don't let drop statement fail");
+        send!(log, conn, create_statement.as_str()).expect("don't let create statement fail");
+        info!(log, "{} ready to go!", injection);
 
-            // NOTE: "1*" here, otherwise an index mutation is missing for each row insertion, thus cannot be detected.
-            enable_failpoint(
-                &log,
-                client,
-                config.status_addr.clone(),
-                "github.com/pingcap/tidb/table/tables/corruptMutations",
-                format!("1*return(\"{}\")", injection),
-            )
-            .await?;
-            let row = table.new_row();
-            let insertion = format!("INSERT INTO {} VALUES ({})", table.name, row.to_string());
-            let update = format!(
-                "UPDATE {} SET {} = {} WHERE {} = {}",
-                table.name,
-                table.cols[0].name,
-                row.cols[0].next().to_string(),
-                table.cols[1].name,
-                row.cols[1].to_string()
-            );
-
-            let res = async {
-                send!(log, conn, "begin optimistic")?;
-                send!(log, conn, insertion.as_str())?;
-                send!(log, conn, "commit")?;
-                send!(log, conn, "begin optimistic")?;
-                send!(log, conn, update.as_str())?;
-                send!(log, conn, "commit")?;
-                Ok(())
-            }
-            .await;
+        let row = table.new_row();
+        let insertion = table.insert(std::slice::from_ref(&row));
+        let update = table.update((0, row.cols[0].next()), (1, row.cols[1].clone()));
+        warm_up(log, &mut conn, session, &[&insertion, &update]).await?;
 
-            if res.is_err() {
-                send!(log, conn, "rollback")?;
-            }
-            info!(log, "workload finished"; "result" => ?res);
-
-            collect_result(res, results, &table, injection, pool.clone()).await;
-            disable_failpoint(
-                &log,
-                client,
-                config.status_addr.clone(),
-                "github.com/pingcap/tidb/table/tables/corruptMutations",
-            )
+        // NOTE: "1*" here, otherwise an index mutation is missing for each row insertion, thus cannot be detected.
+        failpoint
+            .enable(client, injection.value(term, "1*return"))
             .await?;
+
+        let res = async {
+            send!(log, conn, "begin optimistic")?;
+            send_dml!(log, conn, session, insertion)?;
+            send!(log, conn, "commit")?;
+            send!(log, conn, "begin optimistic")?;
+            send_dml!(log, conn, session, update)?;
+            send!(log, conn, "commit")?;
+            Ok(())
         }
+        .await;
+
+        if res.is_err() {
+            send!(log, conn, "rollback")?;
+        }
+        info!(log, "workload finished"; "result" => ?res);
+
+        collect_result(case, self.name(), res, None, results).await;
+        failpoint.disable(client).await?;
 
         Ok(())
     }
@@ -306,80 +632,64 @@ impl Workload for T2 {
 struct T3;
 #[async_trait]
 impl Workload for T3 {
-    async fn execute(
-        &self,
-        log: Logger,
-        config: &Config,
-        table: Table,
-        client: &reqwest::Client,
-        pool: Arc<Pool<MySql>>,
-        results: &mut HashMap<(Table, String, String), Effectiveness>,
-    ) -> Result<()> {
-        let mut conn = pool.acquire().await?;
-        enable_featuers(&mut conn, config).await?;
+    fn name(&self) -> &'static str {
+        "t3"
+    }
+
+    async fn execute(&self, case: &Case, results: &mut Results) -> Result<()> {
+        let Case {
+            log,
+            config,
+            session,
+            term,
+            table,
+            injection,
+            client,
+            pool,
+        } = case;
+        let mut conn = acquire(pool).await?;
+        setup_session(&mut conn, config, session).await?;
         let drop_statement = table.drop_statement();
         let create_statement = table.create_statement();
         let start = Instant::now();
         let duration = start.elapsed();
         CREATE_TABLE_DURAION_MS.fetch_add(duration.as_millis() as u64, Ordering::SeqCst);
 
-        for injection in AVAILABLE_INJECTIONS {
-            send!(log, conn, drop_statement.as_str()).expect("don't let drop statement fail");
-            send!(log, conn, create_statement.as_str()).expect("don't let create statement fail");
-            info!(log, "{} ready to go!", injection);
+        let failpoint = case.failpoint();
+        send!(log, conn, drop_statement.as_str()).expect("don't let drop statement fail");
+        send!(log, conn, create_statement.as_str()).expect("don't let create statement fail");
+        info!(log, "{} ready to go!", injection);
 
-            // NOTE: "1*" here, otherwise an index mutation is missing for each row insertion, thus cannot be detected.
-            enable_failpoint(
-                &log,
-                client,
-                config.status_addr.clone(),
-                "github.com/pingcap/tidb/table/tables/corruptMutations",
-                format!("1*return(\"{}\")", injection),
-            )
+        let row = table.new_row();
+        let insertion = table.insert(std::slice::from_ref(&row));
+        let update = table.update((0, row.cols[0].next()), (1, row.cols[1].clone()));
+        let deletion = table.delete((0, row.cols[1].clone()));
+        warm_up(log, &mut conn, session, &[&insertion, &update, &deletion]).await?;
+
+        // NOTE: "1*" here, otherwise an index mutation is missing for each row insertion, thus cannot be detected.
+        failpoint
+            .enable(client, injection.value(term, "1*return"))
             .await?;
+        send!(log, conn, "begin optimistic")?;
+
+        let res = async {
+            send_dml!(log, conn, session, insertion)?;
+            send!(log, conn, "commit")?;
             send!(log, conn, "begin optimistic")?;
-            let row = table.new_row();
-            let insertion = format!("INSERT INTO {} VALUES ({})", table.name, row.to_string());
-            let update = format!(
-                "UPDATE {} SET {} = {} WHERE {} = {}",
-                table.name,
-                table.cols[0].name,
-                row.cols[0].next().to_string(),
-                table.cols[1].name,
-                row.cols[1].to_string()
-            );
-            let deletion = format!(
-                "DELETE FROM {} WHERE {} = {}",
-                table.name,
-                table.cols[0].name,
-                row.cols[1].to_string()
-            );
-
-            let res = async {
-                send!(log, conn, insertion.as_str())?;
-                send!(log, conn, "commit")?;
-                send!(log, conn, "begin optimistic")?;
-                send!(log, conn, update.as_str())?;
-                send!(log, conn, deletion.as_str())?;
-                send!(log, conn, "commit")?;
-                Ok(())
-            }
-            .await;
+            send_dml!(log, conn, session, update)?;
+            send_dml!(log, conn, session, deletion)?;
+            send!(log, conn, "commit")?;
+            Ok(())
+        }
+        .await;
 
-            if res.is_err() {
-                send!(log, conn, "rollback")?;
-            }
-            info!(log, "workload finished"; "result" => ?res);
-
-            collect_result(res, results, &table, injection, pool.clone()).await;
-            disable_failpoint(
-                &log,
-                client,
-                config.status_addr.clone(),
-                "github.com/pingcap/tidb/table/tables/corruptMutations",
-            )
-            .await?;
+        if res.is_err() {
+            send!(log, conn, "rollback")?;
         }
+        info!(log, "workload finished"; "result" => ?res);
+
+        collect_result(case, self.name(), res, None, results).await;
+        failpoint.disable(client).await?;
 
         Ok(())
     }
@@ -389,74 +699,896 @@ impl Workload for T3 {
 struct T4;
 #[async_trait]
 impl Workload for T4 {
-    async fn execute(
-        &self,
-        log: Logger,
-        config: &Config,
-        table: Table,
-        client: &reqwest::Client,
-        pool: Arc<Pool<MySql>>,
-        results: &mut HashMap<(Table, String, String), Effectiveness>,
-    ) -> Result<()> {
-        let mut conn = pool.acquire().await?;
-        enable_featuers(&mut conn, config).await?;
+    fn name(&self) -> &'static str {
+        "t4"
+    }
+
+    async fn execute(&self, case: &Case, results: &mut Results) -> Result<()> {
+        let Case {
+            log,
+            config,
+            session,
+            term,
+            table,
+            injection,
+            client,
+            pool,
+        } = case;
+        let mut conn = acquire(pool).await?;
+        setup_session(&mut conn, config, session).await?;
         let drop_statement = table.drop_statement();
         let create_statement = table.create_statement();
         let start = Instant::now();
         let duration = start.elapsed();
         CREATE_TABLE_DURAION_MS.fetch_add(duration.as_millis() as u64, Ordering::SeqCst);
 
-        for injection in AVAILABLE_INJECTIONS {
-            send!(log, conn, drop_statement.as_str()).expect("don't let drop statement fail");
-            send!(log, conn, create_statement.as_str()).expect("don't let create statement fail");
-            info!(log, "{} ready to go!", injection);
+        let failpoint = case.failpoint();
+        send!(log, conn, drop_statement.as_str()).expect("don't let drop statement fail");
+        send!(log, conn, create_statement.as_str()).expect("don't let create statement fail");
+        info!(log, "{} ready to go!", injection);
+
+        let row = table.new_row();
+        let insertion = table.insert(std::slice::from_ref(&row));
+        let update = table.update((0, row.cols[0].next()), (1, row.cols[1].clone()));
+        warm_up(log, &mut conn, session, &[&insertion, &update]).await?;
+        send!(log, conn, "begin optimistic")?;
 
+        let res = async {
+            send_dml!(log, conn, session, insertion)?;
+            send!(log, conn, "commit")?;
             send!(log, conn, "begin optimistic")?;
-            let row = table.new_row();
-            let insertion = format!("INSERT INTO {} VALUES ({})", table.name, row.to_string());
-            let update = format!(
-                "UPDATE {} SET {} = {} WHERE {} = {}",
-                table.name,
-                table.cols[0].name,
-                row.cols[0].next().to_string(),
-                table.cols[1].name,
-                row.cols[1].to_string()
-            );
-
-            let res = async {
-                send!(log, conn, insertion.as_str())?;
-                send!(log, conn, "commit")?;
-                send!(log, conn, "begin optimistic")?;
-                // NOTE: "1*" here, otherwise an index mutation is missing for each row insertion, thus cannot be detected.
-                enable_failpoint(
-                    &log,
-                    client,
-                    config.status_addr.clone(),
-                    "github.com/pingcap/tidb/table/tables/corruptMutations",
-                    format!("1*return(\"{}\")", injection),
-                )
-                .await
-                .expect("failed to enable failpoint");
-                send!(log, conn, update.as_str())?;
-                send!(log, conn, "commit")?;
-                Ok(())
+            // NOTE: "1*" here, otherwise an index mutation is missing for each row insertion, thus cannot be detected.
+            failpoint
+                .enable(client, injection.value(term, "1*return"))
+                .await?;
+            send_dml!(log, conn, session, update)?;
+            send!(log, conn, "commit")?;
+            Ok(())
+        }
+        .await;
+
+        if res.is_err() {
+            send!(log, conn, "rollback")?;
+        }
+        let res = outcome(res)?;
+        info!(log, "workload finished"; "result" => ?res);
+        collect_result(case, self.name(), res, None, results).await;
+        failpoint.disable(client).await?;
+
+        Ok(())
+    }
+}
+
+// 1 txn writes 2 rows with corrupted data, then they are read back through all indices and
+// compared with table scans. Check if the read path can detect it.
+struct ReadPath;
+#[async_trait]
+impl Workload for ReadPath {
+    fn name(&self) -> &'static str {
+        "read"
+    }
+
+    async fn execute(&self, case: &Case, results: &mut Results) -> Result<()> {
+        let Case {
+            log,
+            config,
+            session,
+            term,
+            table,
+            injection,
+            client,
+            pool,
+        } = case;
+        let mut conn = acquire(pool).await?;
+        setup_session(&mut conn, config, session).await?;
+        let drop_statement = table.drop_statement();
+        let create_statement = table.create_statement();
+
+        let failpoint = case.failpoint();
+        send!(log, conn, drop_statement.as_str()).expect("don't let drop statement fail");
+        send!(log, conn, create_statement.as_str()).expect("don't let create statement fail");
+        info!(log, "{} ready to go!", injection);
+
+        let rows = vec![table.new_row(), table.new_row().next()];
+        let insertion_1 = table.insert(&rows[..1]);
+        let insertion_2 = table.insert(&rows[1..]);
+        warm_up(log, &mut conn, session, &[&insertion_1, &insertion_2]).await?;
+
+        // "1*" corrupts only the first row, so the batch reads mix it with an intact one
+        failpoint
+            .enable(client, injection.value(term, "1*return"))
+            .await?;
+
+        let res = async {
+            send!(log, conn, "begin optimistic")?;
+            send_dml!(log, conn, session, insertion_1)?;
+            send_dml!(log, conn, session, insertion_2)?;
+            send!(log, conn, "commit")?;
+            Ok(())
+        }
+        .await;
+
+        let reads = if res.is_err() {
+            send!(log, conn, "rollback")?;
+            None
+        } else {
+            Some(check_reads(log, &mut conn, table, &rows).await)
+        };
+        info!(log, "workload finished"; "result" => ?res);
+
+        collect_result(case, self.name(), res, reads, results).await;
+        failpoint.disable(client).await?;
+
+        Ok(())
+    }
+}
+
+// 1 txn writes 2 rows with a savepoint in between, rolls back to the savepoint and commits.
+// The corruption is injected either before the savepoint, so it's kept in the membuffer,
+// or after it, so it's discarded by the partial rollback.
+struct Savepoint {
+    inject_after: bool,
+}
+#[async_trait]
+impl Workload for Savepoint {
+    fn name(&self) -> &'static str {
+        if self.inject_after {
+            "savepoint_after"
+        } else {
+            "savepoint_before"
+        }
+    }
+
+    async fn execute(&self, case: &Case, results: &mut Results) -> Result<()> {
+        let Case {
+            log,
+            config,
+            session,
+            term,
+            table,
+            injection,
+            client,
+            pool,
+        } = case;
+        let mut conn = acquire(pool).await?;
+        setup_session(&mut conn, config, session).await?;
+        let drop_statement = table.drop_statement();
+        let create_statement = table.create_statement();
+
+        let failpoint = case.failpoint();
+        send!(log, conn, drop_statement.as_str()).expect("don't let drop statement fail");
+        send!(log, conn, create_statement.as_str()).expect("don't let create statement fail");
+        info!(log, "{} ready to go!", injection);
+
+        let row = table.new_row();
+        let insertion_1 = table.insert(std::slice::from_ref(&row));
+        let insertion_2 = table.insert(&[row.next()]);
+        warm_up(log, &mut conn, session, &[&insertion_1, &insertion_2]).await?;
+        // "1*" corrupts only the insertion right after enabling, which is the one kept by the partial
+        // rollback or the one discarded by it
+        let enable = || failpoint.enable(client, injection.value(term, "1*return"));
+
+        let res = async {
+            send!(log, conn, "begin optimistic")?;
+            if !self.inject_after {
+                enable().await?;
             }
-            .await;
+            send_dml!(log, conn, session, insertion_1)?;
+            send!(log, conn, "savepoint s1")?;
+            if self.inject_after {
+                enable().await?;
+            }
+            send_dml!(log, conn, session, insertion_2)?;
+            send!(log, conn, "rollback to savepoint s1")?;
+            send!(log, conn, "commit")?;
+            Ok(())
+        }
+        .await;
+
+        if res.is_err() {
+            send!(log, conn, "rollback")?;
+        }
+        let res = outcome(res)?;
+        info!(log, "workload finished"; "result" => ?res);
+
+        collect_result(case, self.name(), res, None, results).await;
+        failpoint.disable(client).await?;
+
+        Ok(())
+    }
+}
+
+// number of connections used by the `conflict` workload
+const CONFLICT_CONCURRENCY: usize = 4;
+
+// several connections concurrently update the same row on the indexed columns, with a mix of
+// optimistic and pessimistic txns, so there are write conflicts, lock waits and automatic retries
+// while the injection is active.
+struct Conflict;
+#[async_trait]
+impl Workload for Conflict {
+    fn name(&self) -> &'static str {
+        "conflict"
+    }
 
-            if res.is_err() {
-                send!(log, conn, "rollback")?;
+    async fn execute(&self, case: &Case, results: &mut Results) -> Result<()> {
+        let Case {
+            log,
+            config,
+            session,
+            term,
+            table,
+            injection,
+            client,
+            pool,
+        } = case;
+        let mut conns = Vec::with_capacity(CONFLICT_CONCURRENCY);
+        for _ in 0..CONFLICT_CONCURRENCY {
+            let mut conn = acquire(pool).await?;
+            setup_session(&mut conn, config, session).await?;
+            // let optimistic txns retry on conflicts
+            send!(conn, "set @@tidb_disable_txn_auto_retry = off")?;
+            conns.push(conn);
+        }
+        let drop_statement = table.drop_statement();
+        let create_statement = table.create_statement();
+
+        let failpoint = case.failpoint();
+        let conn = &mut conns[0];
+        send!(log, conn, drop_statement.as_str()).expect("don't let drop statement fail");
+        send!(log, conn, create_statement.as_str()).expect("don't let create statement fail");
+        let row = table.new_row();
+        send_dml!(log, conn, session, table.insert(std::slice::from_ref(&row)))?;
+        let updates = (0..CONFLICT_CONCURRENCY)
+            .map(|i| table.update((0, row.cols[0].nth(i + 1)), (1, row.cols[1].clone())))
+            .collect::<Vec<_>>();
+        for (conn, update) in conns.iter_mut().zip(&updates) {
+            warm_up(log, conn, session, &[update]).await?;
+        }
+        info!(log, "{} ready to go!", injection);
+
+        // "1*" corrupts only one of the concurrent updates, the others conflict with it
+        failpoint
+            .enable(client, injection.value(term, "1*return"))
+            .await?;
+        let txns = conns
+            .iter_mut()
+            .zip(&updates)
+            .enumerate()
+            .map(|(i, (conn, update))| {
+                let log = log.clone();
+                let mode = if i % 2 == 0 {
+                    "begin optimistic"
+                } else {
+                    "begin pessimistic"
+                };
+                async move {
+                    let res = async {
+                        send!(log, conn, mode)?;
+                        send_dml!(log, conn, session, update)?;
+                        send!(log, conn, "commit")?;
+                        Ok(())
+                    }
+                    .await;
+                    if res.is_err() {
+                        send!(log, conn, "rollback").ok();
+                    }
+                    res
+                }
+            });
+
+        // an inconsistency reported by any txn is a success, conflicts are expected
+        let mut res = Ok(());
+        for r in join_all(txns).await {
+            match r {
+                Err(e) if is_inconsistency_error(&e) => {
+                    res = Err(e);
+                    break;
+                }
+                Err(e) if !is_conflict_error(&e) && res.is_ok() => res = Err(e),
+                _ => {}
             }
-            info!(log, "workload finished"; "result" => ?res);
-            collect_result(res, results, &table, injection, pool.clone()).await;
-            disable_failpoint(
-                &log,
-                client,
-                config.status_addr.clone(),
-                "github.com/pingcap/tidb/table/tables/corruptMutations",
-            )
+        }
+        info!(log, "workload finished"; "result" => ?res);
+
+        collect_result(case, self.name(), res, None, results).await;
+        failpoint.disable(client).await?;
+
+        Ok(())
+    }
+}
+
+// number of rows in each insert statement of the `large` workload
+const LARGE_TXN_BATCH_SIZE: usize = 100;
+
+// 1 txn inserts `txn_size` rows and then updates all of them, so that the membuffer is big and 2PC
+// splits the mutations into many batches. The `inject_at`-th row write is corrupted.
+struct LargeTxn;
+#[async_trait]
+impl Workload for LargeTxn {
+    fn name(&self) -> &'static str {
+        "large"
+    }
+
+    async fn execute(&self, case: &Case, results: &mut Results) -> Result<()> {
+        let Case {
+            log,
+            config,
+            session,
+            term,
+            table,
+            injection,
+            client,
+            pool,
+        } = case;
+        let mut conn = acquire(pool).await?;
+        setup_session(&mut conn, config, session).await?;
+        let drop_statement = table.drop_statement();
+        let create_statement = table.create_statement();
+        let row = table.new_row();
+        let rows = (0..config.txn_size as usize)
+            .map(|i| row.nth(i))
+            .collect::<Vec<_>>();
+        let insertions = rows
+            .chunks(LARGE_TXN_BATCH_SIZE)
+            .map(|chunk| table.insert(chunk))
+            .collect::<Vec<_>>();
+        // the updated values are clear of the inserted ones, so unique keys don't conflict
+        let update = table.update_all(rows.len());
+
+        let failpoint = case.failpoint();
+        send!(log, conn, drop_statement.as_str()).expect("don't let drop statement fail");
+        send!(log, conn, create_statement.as_str()).expect("don't let create statement fail");
+        // all batches but the last one have the same statement
+        let batches = [
+            insertions.first().unwrap(),
+            insertions.last().unwrap(),
+            &update,
+        ];
+        warm_up(log, &mut conn, session, &batches).await?;
+        info!(log, "{} ready to go!", injection);
+
+        // the failpoint is evaluated once per row write, skip the writes before `inject_at`
+        let default = if config.inject_at == 0 {
+            "1*return".to_owned()
+        } else {
+            format!("{}*off->1*return", config.inject_at)
+        };
+        failpoint
+            .enable(client, injection.value(term, &default))
             .await?;
+
+        let res = async {
+            send!(conn, "begin optimistic")?;
+            for dml in insertions.iter().chain([&update]) {
+                match session.protocol {
+                    StatementProtocol::Text => send!(conn, dml.to_text().as_str())?,
+                    StatementProtocol::Binary => {
+                        let id = conn.id;
+                        send!(conn, prepared(id, dml))?
+                    }
+                };
+            }
+            send!(log, conn, "commit")?;
+            Ok(())
+        }
+        .await;
+
+        if res.is_err() {
+            send!(log, conn, "rollback")?;
+        }
+        info!(log, "workload finished"; "result" => ?res, "txn size" => config.txn_size, "inject at" => config.inject_at);
+
+        collect_result(case, self.name(), res, None, results)
+            .await
+            .txn_size = Some(config.txn_size as usize);
+        failpoint.disable(client).await?;
+
+        Ok(())
+    }
+}
+
+// number of rows inserted before the DDL starts in the online DDL workloads
+const DDL_INITIAL_ROWS: usize = 10;
+// upper bound of DML statements sent while the DDL is running
+const DDL_MAX_DML: usize = 1000;
+
+enum DdlKind {
+    AddIndex,
+    ModifyColumn,
+}
+
+// some rows are inserted, then a DDL that reorganizes data (index backfill or column type change)
+// runs in another connection while DML keeps writing, with the injection active once the DDL
+// reorganizes data. The DDL writes index entries without going through the normal DML path.
+struct OnlineDdl(DdlKind);
+#[async_trait]
+impl Workload for OnlineDdl {
+    fn name(&self) -> &'static str {
+        match self.0 {
+            DdlKind::AddIndex => "add_index",
+            DdlKind::ModifyColumn => "modify_column",
         }
+    }
+
+    async fn execute(&self, case: &Case, results: &mut Results) -> Result<()> {
+        let Case {
+            log,
+            config,
+            session,
+            term,
+            table,
+            injection,
+            client,
+            pool,
+        } = case;
+        let mut conn = acquire(pool).await?;
+        setup_session(&mut conn, config, session).await?;
+        // the DDL runs on the same instance and with the same variables as the DML
+        let mut ddl_conn = acquire(pool).await?;
+        setup_session(&mut ddl_conn, config, session).await?;
+        let drop_statement = table.drop_statement();
+        let create_statement = table.create_statement();
+        // the data is checked as the table the DDL leaves, e.g. with the added index
+        let (ddl_statement, altered) = match self.0 {
+            DdlKind::AddIndex => table.add_index(),
+            DdlKind::ModifyColumn => match table.modify_column() {
+                Some(ddl) => ddl,
+                None => {
+                    info!(log, "skipped, all columns are in the primary key"; "table" => &table.name);
+                    return Ok(());
+                }
+            },
+        };
+        let row = table.new_row();
+        let initial_rows = (0..DDL_INITIAL_ROWS)
+            .map(|i| row.nth(i))
+            .collect::<Vec<_>>();
+        let insertions = (DDL_INITIAL_ROWS..DDL_INITIAL_ROWS + DDL_MAX_DML)
+            .map(|i| table.insert(&[row.nth(i)]))
+            .collect::<Vec<_>>();
+
+        let failpoint = case.failpoint();
+        send!(log, conn, drop_statement.as_str()).expect("don't let drop statement fail");
+        send!(log, conn, create_statement.as_str()).expect("don't let create statement fail");
+        send_dml!(log, conn, session, table.insert(&initial_rows))?;
+        warm_up(log, &mut conn, session, &[&insertions[0]]).await?;
+        // watches the state of the DDL, its queries are not a part of the case
+        let mut state_conn = pool.acquire().await?;
+        info!(log, "{} ready to go!", injection);
+
+        let ddl_done = AtomicBool::new(false);
+        let injected = AtomicBool::new(false);
+        let ddl = async {
+            let res = send!(log, ddl_conn, ddl_statement.as_str()).map(|_| ());
+            ddl_done.store(true, Ordering::SeqCst);
+            res
+        };
+        // The DML before the reorg isn't corrupted, or the case would end before the DDL gets to
+        // the data. From the reorg on, every insertion is corrupted until the DDL is done.
+        let dml = async {
+            for insertion in &insertions {
+                if ddl_done.load(Ordering::SeqCst) {
+                    break;
+                }
+                if !injected.load(Ordering::SeqCst)
+                    && is_reorganizing(&mut state_conn, table).await?
+                {
+                    failpoint
+                        .enable(client, injection.value(term, "return"))
+                        .await?;
+                    injected.store(true, Ordering::SeqCst);
+                }
+                send_dml!(log, conn, session, insertion)?;
+            }
+            Ok(())
+        };
+        let (ddl_res, dml_res) = tokio::join!(ddl, dml);
+        info!(log, "ddl finished"; "result" => ?ddl_res);
+        let dml_res = outcome(dml_res)?;
+        if !injected.load(Ordering::SeqCst) {
+            info!(log, "skipped, the reorg isn't seen before the DDL is done"; "table" => &table.name, "result" => ?dml_res);
+            return Ok(());
+        }
+
+        // an inconsistency reported by either side is a success
+        let res = match (ddl_res, dml_res) {
+            (Err(e), _) | (_, Err(e)) if is_inconsistency_error(&e) => Err(e),
+            (Err(e), _) | (_, Err(e)) => Err(e),
+            _ => Ok(()),
+        };
+        info!(log, "workload finished"; "result" => ?res);
+
+        collect_result_as(case, &altered, self.name(), res, None, results).await;
+        failpoint.disable(client).await?;
+
+        Ok(())
+    }
+}
+
+// whether a DDL on the table is reorganizing its data, i.e. backfilling the index or the column
+async fn is_reorganizing(conn: &mut MySqlConnection, table: &Table) -> Result<bool> {
+    let rows = conn
+        .fetch_all(
+            format!(
+                "SELECT 1 FROM information_schema.ddl_jobs WHERE db_name = DATABASE() AND table_name = '{}' AND state = 'running' AND schema_state = 'write reorganization'",
+                table.name
+            )
+            .as_str(),
+        )
+        .await?;
+    Ok(!rows.is_empty())
+}
+
+// number of rows in the non-transactional DML workloads
+const BATCH_ROWS: usize = 10;
+// number of rows in each job of the non-transactional DML
+const BATCH_LIMIT: usize = 2;
+
+// a non-transactional DML (`BATCH ON ... LIMIT ...`) deletes or updates all rows in many small txns
+// while the injection is active. It also records how many of the jobs succeeded.
+struct NonTransactional {
+    update: bool,
+}
+#[async_trait]
+impl Workload for NonTransactional {
+    fn name(&self) -> &'static str {
+        if self.update {
+            "batch_update"
+        } else {
+            "batch_delete"
+        }
+    }
+
+    async fn execute(&self, case: &Case, results: &mut Results) -> Result<()> {
+        let Case {
+            log,
+            config,
+            session,
+            term,
+            table,
+            injection,
+            client,
+            pool,
+        } = case;
+        let mut conn = acquire(pool).await?;
+        setup_session(&mut conn, config, session).await?;
+        let drop_statement = table.drop_statement();
+        let create_statement = table.create_statement();
+        let row = table.new_row();
+        let rows = (0..BATCH_ROWS).map(|i| row.nth(i)).collect::<Vec<_>>();
+        let batch_statement = table.batch_statement(BATCH_LIMIT, self.update);
+
+        let failpoint = case.failpoint();
+        send!(log, conn, drop_statement.as_str()).expect("don't let drop statement fail");
+        send!(log, conn, create_statement.as_str()).expect("don't let create statement fail");
+        send_dml!(log, conn, session, table.insert(&rows))?;
+        info!(log, "{} ready to go!", injection);
+
+        // "1*" corrupts a single mutation, so at most one job fails by the injection and the others
+        // show whether the DML goes on after it
+        failpoint
+            .enable(client, injection.value(term, "1*return"))
+            .await?;
+
+        info!(log, "executing"; "query" => &batch_statement);
+        let id = conn.id;
+        let res = conn.fetch_all(traced(id, batch_statement.as_str())).await;
+        record_returned(id);
+        // (succeeded, total) as TiDB counts them, they are unknown if it doesn't tell
+        let jobs = match &res {
+            Ok(rows) => rows
+                .first()
+                .and_then(|row| row.try_get::<i64, _>(0).ok())
+                .map(|total| (total as usize, total as usize)),
+            Err(e) => failed_jobs(e).map(|(failed, total)| (total - failed, total)),
+        };
+        let res = res.map(|_| ());
+        info!(log, "workload finished"; "result" => ?res, "jobs" => ?jobs);
+
+        collect_result(case, self.name(), res, None, results)
+            .await
+            .jobs = jobs;
+        failpoint.disable(client).await?;
 
         Ok(())
     }
 }
+
+// parse (failed jobs, total jobs) from the error of a non-transactional DML. If it succeeds, the
+// total is the `number of jobs` it returns instead.
+// If the first job fails, the others are canceled and the error doesn't contain the numbers.
+fn failed_jobs(e: &sqlx::Error) -> Option<(usize, usize)> {
+    lazy_static! {
+        static ref RE: Regex = Regex::new(r"(\d+)/(\d+) jobs failed").unwrap();
+    }
+    let msg = e.to_string();
+    let captures = RE.captures(&msg)?;
+    Some((
+        captures.get(1).unwrap().as_str().parse().unwrap(),
+        captures.get(2).unwrap().as_str().parse().unwrap(),
+    ))
+}
+
+// random sequences of insert/replace/update/delete in random txns, see `fuzz::Program`.
+// The seed of each case is logged, and `--seed` replays it.
+struct Fuzz;
+#[async_trait]
+impl Workload for Fuzz {
+    fn name(&self) -> &'static str {
+        "fuzz"
+    }
+
+    async fn execute(&self, case: &Case, results: &mut Results) -> Result<()> {
+        let program = Program::generate(fuzz_seed(case.config, case.table, case.injection.name));
+        fuzz_case(case, &program, results).await
+    }
+}
+
+lazy_static! {
+    // the fuzz programs of a process are derived from it, unless `--seed` is given
+    static ref FUZZ_RUN_SEED: u64 = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_nanos() as u64;
+}
+
+// the seed of the fuzz program of a case. It's logged, and `--seed` replays the program.
+pub fn fuzz_seed(config: &Config, table: &Table, injection: &str) -> u64 {
+    config.seed.unwrap_or_else(|| {
+        let mut hasher = DefaultHasher::new();
+        (*FUZZ_RUN_SEED, &table.name, injection).hash(&mut hasher);
+        hasher.finish()
+    })
+}
+
+// a case of the fuzz workload. The shrinker also runs it with shortened programs.
+pub async fn fuzz_case(case: &Case<'_>, program: &Program, results: &mut Results) -> Result<()> {
+    let Case {
+        log,
+        config,
+        session,
+        table,
+        injection,
+        client,
+        pool,
+        ..
+    } = case;
+    let mut conn = acquire(pool).await?;
+    setup_session(&mut conn, config, session).await?;
+    send!(log, conn, table.drop_statement().as_str()).expect("don't let drop statement fail");
+    send!(log, conn, table.create_statement().as_str()).expect("don't let create statement fail");
+    info!(log, "{} ready to go!", injection; "seed" => program.seed, "steps" => ?program.steps, "inject at" => program.inject_at);
+
+    let failpoint = case.failpoint();
+    let res = run_program(case, &mut conn, &failpoint, program).await;
+
+    if res.is_err() {
+        send!(log, conn, "rollback")?;
+    }
+    let res = outcome(res)?;
+    info!(log, "workload finished"; "result" => ?res, "seed" => program.seed);
+
+    collect_result(case, Fuzz.name(), res, None, results).await;
+    failpoint.disable(client).await?;
+    Ok(())
+}
+
+// run the steps of a fuzz program, the failpoint is enabled right before the `inject_at`-th one.
+async fn run_program(
+    case: &Case<'_>,
+    conn: &mut Conn,
+    failpoint: &FailpointGuard,
+    program: &Program,
+) -> Result<()> {
+    let Case {
+        log,
+        session,
+        term,
+        table,
+        injection,
+        client,
+        ..
+    } = case;
+    for (i, step) in program.steps.iter().enumerate() {
+        if i == program.inject_at {
+            // "1*" corrupts only the `inject_at`-th step, which the shrinker keeps in shorter programs
+            failpoint
+                .enable(client, injection.value(term, "1*return"))
+                .await?;
+        }
+        let res = match (step.control(), step.dml(table)) {
+            (Some(control), _) => send!(log, conn, control),
+            (_, Some(dml)) => send_dml!(log, conn, session, dml),
+            _ => unreachable!(),
+        };
+        match res {
+            // duplicate keys are expected in random sequences, the statement (or the optimistic
+            // txn if it's found at commit) is rolled back and the program goes on
+            Err(e) if is_duplicate_error(&e) => info!(log, "ignored error"; "error" => ?e),
+            res => {
+                res?;
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::{find_workload, Case};
+    use crate::config::{parse_args, Config};
+    use crate::failpoint::new_client;
+    use crate::injection::Injection;
+    use crate::mock::{Call, MockMySqlServer, MockStatusServer, Reply};
+    use crate::table::Table;
+    use crate::{Effectiveness, Results};
+    use slog::{o, Discard, Logger};
+    use sqlx::mysql::MySqlPoolOptions;
+    use std::sync::Arc;
+
+    fn config(workload: &str, uri: &str, status: &MockStatusServer) -> Config {
+        parse_args([
+            "corrupttest",
+            "-a",
+            "strict",
+            "-m",
+            "1",
+            "-w",
+            workload,
+            "-u",
+            uri,
+            "-s",
+            &status.addr,
+        ])
+    }
+
+    // the failpoint is enabled once per injection, and disabled before the next one
+    #[tokio::test(flavor = "multi_thread")]
+    async fn t2_against_mock() {
+        let status = MockStatusServer::start().await.unwrap();
+        let mysql = MockMySqlServer::start(status.ddl_id()).await.unwrap();
+        // extraIndex is detected on commit, missingIndex is only found by admin check
+        let failpoints = status.clone();
+        mysql.on(move |sql| {
+            let value = failpoints.enabled().into_values().next()?;
+            if sql == "commit" && value.contains("extraIndex") {
+                Some(Reply::assertion_failed())
+            } else if sql.starts_with("admin check") && value.contains("missingIndex") {
+                Some(Reply::data_inconsistent("t"))
+            } else {
+                None
+            }
+        });
+        let uri = mysql.uri();
+        let config = config("t2", &uri, &status);
+        let log = Logger::root(Discard, o!());
+        let client = new_client().unwrap();
+        let pool = Arc::new(MySqlPoolOptions::new().connect(&uri).await.unwrap());
+        let table =
+            Table::from_create_statement("CREATE TABLE t (c1 INT, c2 INT, KEY i1 (c1))").unwrap();
+        let injections = Injection::applicable_to(&config, &table);
+        let mut results = Results::new();
+        let session = &config.sessions()[0];
+        for injection in &injections {
+            let case = Case {
+                log: log.clone(),
+                config: &config,
+                session,
+                term: &config.terms[0],
+                table: &table,
+                injection,
+                client: &client,
+                pool: pool.clone(),
+            };
+            find_workload("t2")
+                .execute(&case, &mut results)
+                .await
+                .unwrap();
+        }
+
+        let mut classified = results
+            .iter()
+            .map(|(key, value)| (key.2.as_str(), format!("{:?}", value.effectiveness)))
+            .collect::<Vec<_>>();
+        classified.sort();
+        assert_eq!(
+            classified,
+            vec![
+                ("corruptIndexKey", "Consistent".to_owned()),
+                ("corruptIndexValue", "Consistent".to_owned()),
+                ("extraIndex", "Success".to_owned()),
+                ("missingIndex", "Failure(Some(\"i1\"))".to_owned()),
+            ]
+        );
+        let expected_calls = injections
+            .iter()
+            .flat_map(|injection| {
+                [
+                    Call::Enable {
+                        name: injection.failpoint.to_owned(),
+                        value: injection.value(&config.terms[0], "1*return"),
+                    },
+                    Call::Disable {
+                        name: injection.failpoint.to_owned(),
+                    },
+                ]
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(status.calls(), expected_calls);
+    }
+
+    // a failpoint that can't be enabled in the middle of the txn leaves no result
+    #[tokio::test(flavor = "multi_thread")]
+    async fn savepoint_returns_failpoint_error() {
+        let status = MockStatusServer::start().await.unwrap();
+        let mysql = MockMySqlServer::start(status.ddl_id()).await.unwrap();
+        let uri = mysql.uri();
+        let config = config("savepoint_after", &uri, &status);
+        let client = new_client().unwrap();
+        let pool = Arc::new(MySqlPoolOptions::new().connect(&uri).await.unwrap());
+        let table =
+            Table::from_create_statement("CREATE TABLE t (c1 INT, c2 INT, KEY i1 (c1))").unwrap();
+        let case = Case {
+            log: Logger::root(Discard, o!()),
+            config: &config,
+            session: &config.sessions()[0],
+            term: &config.terms[0],
+            table: &table,
+            injection: Injection::find("missingIndex").unwrap(),
+            client: &client,
+            pool,
+        };
+        status.fail_next(400);
+        let mut results = Results::new();
+        let res = find_workload("savepoint_after")
+            .execute(&case, &mut results)
+            .await;
+
+        assert!(res.is_err());
+        assert!(results.is_empty());
+        let statements = mysql.statements();
+        let savepoint = statements.iter().position(|s| s == "savepoint s1").unwrap();
+        assert_eq!(statements[savepoint + 1], "rollback");
+        assert!(status.enabled().is_empty());
+    }
+
+    // the jobs of a non-transactional DML are kept in the result, even if some of them fail
+    #[tokio::test(flavor = "multi_thread")]
+    async fn batch_delete_reports_jobs() {
+        let status = MockStatusServer::start().await.unwrap();
+        let mysql = MockMySqlServer::start(status.ddl_id()).await.unwrap();
+        mysql.on(|sql| {
+            sql.starts_with("BATCH").then(|| Reply::Error {
+                code: 1105,
+                message: "2/5 jobs failed in the non-transactional DML: txn 1, assertion failed"
+                    .to_owned(),
+            })
+        });
+        let uri = mysql.uri();
+        let config = config("batch_delete", &uri, &status);
+        let client = new_client().unwrap();
+        let pool = Arc::new(MySqlPoolOptions::new().connect(&uri).await.unwrap());
+        let table =
+            Table::from_create_statement("CREATE TABLE t (c1 INT, c2 INT, KEY i1 (c1))").unwrap();
+        let case = Case {
+            log: Logger::root(Discard, o!()),
+            config: &config,
+            session: &config.sessions()[0],
+            term: &config.terms[0],
+            table: &table,
+            injection: Injection::find("extraIndex").unwrap(),
+            client: &client,
+            pool,
+        };
+        let mut results = Results::new();
+        find_workload("batch_delete")
+            .execute(&case, &mut results)
+            .await
+            .unwrap();
+
+        let result = &results[&case.key("batch_delete")];
+        assert!(matches!(result.effectiveness, Effectiveness::Success));
+        assert_eq!(result.jobs, Some((3, 5)));
+    }
+}
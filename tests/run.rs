@@ -0,0 +1,94 @@
+// Runs the binary against the mock TiDB, from the preflight to the printed result.
+use corrupttest::mock::{Call, MockMySqlServer, MockStatusServer, Reply};
+use std::collections::HashMap;
+use tokio::process::Command;
+
+// the counts of each injection in the result logged by `print_result`, summed over sessions
+fn counts(log: &str) -> HashMap<String, HashMap<String, u32>> {
+    let mut counts: HashMap<String, HashMap<String, u32>> = HashMap::new();
+    for line in log.lines().filter(|l| l.contains(":\tsuccess:")) {
+        let (head, fields) = line.split_once(":\t").unwrap();
+        let injection = head.rsplit(' ').next().unwrap().to_owned();
+        let entry = counts.entry(injection).or_default();
+        for field in fields.split('\t') {
+            let (key, value) = field.split_once(':').unwrap();
+            if let Ok(value) = value.trim().parse::<u32>() {
+                *entry.entry(key.to_owned()).or_default() += value;
+            }
+        }
+    }
+    counts
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn single_against_mock() {
+    let status = MockStatusServer::start().await.unwrap();
+    let mysql = MockMySqlServer::start(status.ddl_id()).await.unwrap();
+    // extraIndex is detected by the insertion, missingIndex is only found by admin check
+    let failpoints = status.clone();
+    mysql.on(move |sql| {
+        let value = failpoints.enabled().into_values().next()?;
+        if sql.to_lowercase().starts_with("insert") && value.contains("extraIndex") {
+            Some(Reply::inconsistent_index("t0"))
+        } else if sql.starts_with("admin check table") && value.contains("missingIndex") {
+            Some(Reply::data_inconsistent("t0"))
+        } else {
+            None
+        }
+    });
+
+    let log_path = std::env::temp_dir().join(format!("corrupttest-{}.log", std::process::id()));
+    let output = Command::new(env!("CARGO_BIN_EXE_corrupttest"))
+        .args(["-a", "strict", "-m", "1", "-w", "single", "-l", "3"])
+        .args(["-p", "text,binary", "-u", &mysql.uri(), "-s", &status.addr])
+        .arg("-o")
+        .arg(&log_path)
+        .output()
+        .await
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "{:?} {}",
+        output.status,
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let log = std::fs::read_to_string(&log_path).unwrap();
+    std::fs::remove_file(&log_path).ok();
+
+    let counts = counts(&log);
+    assert!(counts["extraIndex"]["success"] > 0);
+    assert_eq!(counts["extraIndex"]["failure"], 0);
+    assert!(counts["missingIndex"]["failure"] > 0);
+    assert_eq!(counts["missingIndex"]["success"], 0);
+    assert_eq!(counts["corruptIndexKey"]["success"], 0);
+    assert!(log.contains("expected injection is not detected"));
+    assert!(log
+        .lines()
+        .filter(|l| l.contains(":\tsuccess:"))
+        .all(|l| l.contains("\tworkload:single\t")));
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("missingIndex"));
+
+    // every failpoint is disabled right after it's enabled, the first one is the probe
+    let calls = status.calls();
+    let toggles = calls
+        .iter()
+        .filter(|c| !matches!(c, Call::Get { .. }))
+        .collect::<Vec<_>>();
+    assert!(
+        matches!(toggles[0], Call::Enable { name, value } if name.ends_with("corruptMutations") && value == "1*return(\"missingIndex\")")
+    );
+    for pair in toggles.chunks(2) {
+        match pair {
+            [Call::Enable { name, .. }, Call::Disable { name: disabled }] => {
+                assert_eq!(name, disabled)
+            }
+            _ => panic!("unpaired failpoint calls: {:?}", pair),
+        }
+    }
+    assert!(status.enabled().is_empty());
+    assert!(mysql
+        .statements()
+        .iter()
+        .any(|s| s.starts_with("set @@tidb_txn_assertion_level = strict")));
+}